@@ -0,0 +1,43 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use expedition::{Color32, IntoMessage, Message, Styleable};
+
+fn chat_message() -> Message {
+    "[".color(Color32::DARK_GRAY)
+        .with("Server".color(Color32::GOLD).bold())
+        .with("] ".color(Color32::DARK_GRAY))
+        .with("Player".color(Color32::LIGHT_BLUE).underline())
+        .with(": ")
+        .with("Hello everyone, ")
+        .with("welcome".italic())
+        .with(" to the ")
+        .with("expedition".color(Color32::GREEN).bold().italic())
+        .with("!")
+}
+
+fn encode(c: &mut Criterion) {
+    let msg = chat_message();
+    let mut group = c.benchmark_group("encode");
+    group.bench_function("binary", |b| b.iter(|| black_box(&msg).to_bytes()));
+    group.bench_function("json", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&msg)).unwrap())
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let msg = chat_message();
+    let binary = msg.to_bytes();
+    let json = serde_json::to_vec(&msg).unwrap();
+
+    let mut group = c.benchmark_group("decode");
+    group.bench_function("binary", |b| {
+        b.iter(|| Message::from_bytes(black_box(&binary)).unwrap())
+    });
+    group.bench_function("json", |b| {
+        b.iter(|| serde_json::from_slice::<Message>(black_box(&json)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
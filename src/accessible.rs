@@ -0,0 +1,290 @@
+//! Features for converting [`Message`]s into plain text which keeps the meaning of their styles,
+//! for screen readers and other outputs which cannot show styling.
+//!
+//! The [`fmt::Display`] implementation of [`Message`] removes all styling, which loses
+//! information when a style is the only signal of what text means, such as red text for errors.
+//! [`StyleToAccessible`] instead describes colors with names, and can show emphasis with
+//! markers or in upper case.
+//!
+//! Messages have no concept of links, so only styles can be annotated.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{accessible::{Emphasis, StyleToAccessible}, Color32, IntoMessage, Styleable};
+//!
+//! let msg = "Connection failed: "
+//!     .color(Color32::RED)
+//!     .with("retry".bold())
+//!     .with(" later");
+//!
+//! let style_to_accessible = StyleToAccessible {
+//!     bold: Emphasis::Uppercase,
+//!     color_names: vec![(Color32::RED, "error".to_owned())],
+//!     ..Default::default()
+//! };
+//! assert_eq!(
+//!     "[error: Connection failed: RETRY later]",
+//!     style_to_accessible.to_text(&msg),
+//! );
+//! ```
+//!
+//! [`fmt::Display`]: std::fmt::Display
+
+use std::borrow::Cow;
+
+use crate::{Color32, Message, MessageStyle, StackFlattener};
+
+/// How text with a decoration such as bold is shown in plain text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Emphasis {
+    /// The decoration is removed.
+    Ignore,
+    /// The text is converted to upper case.
+    Uppercase,
+    /// The text is surrounded by markers.
+    Markers {
+        /// Inserted before the text.
+        start: Cow<'static, str>,
+        /// Inserted after the text.
+        end: Cow<'static, str>,
+    },
+}
+
+impl Emphasis {
+    /// Surrounds text with the same `marker` on both sides.
+    #[must_use]
+    pub const fn marker(marker: &'static str) -> Self {
+        Self::Markers {
+            start: Cow::Borrowed(marker),
+            end: Cow::Borrowed(marker),
+        }
+    }
+
+    const fn has_markers(&self) -> bool {
+        matches!(self, Self::Markers { .. })
+    }
+}
+
+/// Defines how a [`Message`] is converted into accessible plain text.
+///
+/// Each color in [`StyleToAccessible::color_names`] is annotated by surrounding text of that
+/// color with `[name: ` and `]`. Other colors, and [`MessageStyle::size`] and
+/// [`MessageStyle::family`], are ignored.
+///
+/// Markers are nested, with color annotations outermost, followed by bold, italic, underline
+/// and strikethrough. When a style changes, markers of the styles inside of it are closed and
+/// reopened, so that markers never overlap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StyleToAccessible {
+    /// How bold text is shown, by default surrounded by `*`.
+    pub bold: Emphasis,
+    /// How italic text is shown, by default surrounded by `_`.
+    pub italic: Emphasis,
+    /// How underlined text is shown, by default ignored.
+    pub underline: Emphasis,
+    /// How strikethrough text is shown, by default surrounded by `~~`.
+    pub strikethrough: Emphasis,
+    /// Names describing the meaning of colors, such as `"error"` for red. Empty by default.
+    pub color_names: Vec<(Color32, String)>,
+}
+
+impl Default for StyleToAccessible {
+    fn default() -> Self {
+        Self {
+            bold: Emphasis::marker("*"),
+            italic: Emphasis::marker("_"),
+            underline: Emphasis::Ignore,
+            strikethrough: Emphasis::marker("~~"),
+            color_names: Vec::new(),
+        }
+    }
+}
+
+/// Number of kinds of annotation, from outermost to innermost.
+const LEVELS: usize = 5;
+
+impl StyleToAccessible {
+    /// Converts a message into plain text, annotating its styles.
+    pub fn to_text(&self, message: &Message) -> String {
+        let mut writer = AccessibleWriter {
+            config: self,
+            out: String::new(),
+            open: [None; LEVELS],
+        };
+        message.flatten(&mut StackFlattener::new(|content, style| {
+            writer.segment(content, style);
+        }));
+        writer.close_from(0);
+        writer.out
+    }
+
+    const fn emphasis(&self, level: usize) -> Option<&Emphasis> {
+        match level {
+            1 => Some(&self.bold),
+            2 => Some(&self.italic),
+            3 => Some(&self.underline),
+            4 => Some(&self.strikethrough),
+            _ => None,
+        }
+    }
+
+    /// Gets which annotation with markers `style` has at each level, as an index into
+    /// [`StyleToAccessible::color_names`] for colors.
+    fn annotations(&self, style: MessageStyle) -> [Option<usize>; LEVELS] {
+        let color = style.color.and_then(|color| {
+            self.color_names
+                .iter()
+                .position(|(named, _)| *named == color)
+        });
+        let mut annotations = [color, None, None, None, None];
+        for (level, flag) in decorations(style).into_iter().enumerate() {
+            let level = level + 1;
+            if flag == Some(true) && self.emphasis(level).is_some_and(Emphasis::has_markers) {
+                annotations[level] = Some(0);
+            }
+        }
+        annotations
+    }
+
+    fn uppercase(&self, style: MessageStyle) -> bool {
+        decorations(style)
+            .into_iter()
+            .enumerate()
+            .any(|(level, flag)| {
+                flag == Some(true) && self.emphasis(level + 1) == Some(&Emphasis::Uppercase)
+            })
+    }
+}
+
+/// Gets the decorations of `style`, in the order of their levels after colors.
+const fn decorations(style: MessageStyle) -> [Option<bool>; LEVELS - 1] {
+    [
+        style.bold,
+        style.italic,
+        style.underline,
+        style.strikethrough,
+    ]
+}
+
+/// Writes segments of text, opening and closing markers as their styles change.
+struct AccessibleWriter<'a> {
+    config: &'a StyleToAccessible,
+    out: String,
+    /// Annotation currently open at each level.
+    open: [Option<usize>; LEVELS],
+}
+
+impl AccessibleWriter<'_> {
+    fn segment(&mut self, content: &str, style: MessageStyle) {
+        if content.is_empty() {
+            return;
+        }
+
+        let wanted = self.config.annotations(style);
+        if let Some(changed) = (0..LEVELS).find(|&level| self.open[level] != wanted[level]) {
+            self.close_from(changed);
+            for (level, annotation) in wanted.iter().enumerate().skip(changed) {
+                if let Some(annotation) = annotation {
+                    self.open_marker(level, *annotation);
+                }
+            }
+            self.open = wanted;
+        }
+
+        if self.config.uppercase(style) {
+            self.out.push_str(&content.to_uppercase());
+        } else {
+            self.out.push_str(content);
+        }
+    }
+
+    fn open_marker(&mut self, level: usize, annotation: usize) {
+        match self.config.emphasis(level) {
+            Some(Emphasis::Markers { start, .. }) => self.out.push_str(start),
+            Some(_) => {}
+            None => {
+                self.out.push('[');
+                self.out.push_str(&self.config.color_names[annotation].1);
+                self.out.push_str(": ");
+            }
+        }
+    }
+
+    /// Closes the open markers at `level` and all levels inside of it.
+    fn close_from(&mut self, level: usize) {
+        for inner in (level..LEVELS).rev() {
+            if self.open[inner].take().is_none() {
+                continue;
+            }
+            match self.config.emphasis(inner) {
+                Some(Emphasis::Markers { end, .. }) => self.out.push_str(end),
+                Some(_) => {}
+                None => self.out.push(']'),
+            }
+        }
+    }
+}
+
+impl Message {
+    /// Converts this message into plain text, using the default [`StyleToAccessible`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let msg = "This is ".with("very".bold().italic()).with(" important");
+    /// assert_eq!("This is *_very_* important", msg.to_accessible_text());
+    /// ```
+    #[must_use]
+    pub fn to_accessible_text(&self) -> String {
+        StyleToAccessible::default().to_text(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color32, IntoMessage, Styleable};
+
+    use super::{Emphasis, StyleToAccessible};
+
+    #[test]
+    fn nesting() {
+        let style_to_accessible = StyleToAccessible {
+            color_names: vec![
+                (Color32::RED, "error".to_owned()),
+                (Color32::GREEN, "ok".to_owned()),
+            ],
+            ..Default::default()
+        };
+        let msg = "a"
+            .bold()
+            .with("b".italic())
+            .with("c".color(Color32::RED))
+            .with("d".color(Color32::GREEN).no_bold())
+            .with("e".color(Color32::BLUE));
+        assert_eq!(
+            "*a_b_*[error: *c*][ok: d]*e*",
+            style_to_accessible.to_text(&msg),
+        );
+    }
+
+    #[test]
+    fn emphasis() {
+        let style_to_accessible = StyleToAccessible {
+            bold: Emphasis::Ignore,
+            italic: Emphasis::Uppercase,
+            underline: Emphasis::Markers {
+                start: "<".into(),
+                end: ">".into(),
+            },
+            ..Default::default()
+        };
+        let msg = "one "
+            .bold()
+            .with("two ".italic())
+            .with("three".underline().strikethrough());
+        assert_eq!("one TWO <~~three~~>", style_to_accessible.to_text(&msg),);
+        assert_eq!("", style_to_accessible.to_text(&"".bold()));
+    }
+}
@@ -0,0 +1,103 @@
+//! Conversions between [`Color32`] and the 256-color palette used by terminals.
+//!
+//! The palette is made of:
+//! - 16 standard colors, which terminals may theme - the xterm defaults are used here
+//! - a 6x6x6 color cube
+//! - a 24-step grayscale ramp
+
+use crate::Color32;
+
+/// How a [`Color32`] is converted to a terminal color.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ColorMode {
+    /// Use 24-bit RGB colors.
+    #[default]
+    Rgb,
+    /// Use the closest color from the 256-color palette, as given by [`to_indexed`].
+    Indexed,
+}
+
+/// The xterm default values of the 16 standard colors.
+pub const STANDARD: [Color32; 16] = [
+    Color32::from_rgb(0, 0, 0),
+    Color32::from_rgb(205, 0, 0),
+    Color32::from_rgb(0, 205, 0),
+    Color32::from_rgb(205, 205, 0),
+    Color32::from_rgb(0, 0, 238),
+    Color32::from_rgb(205, 0, 205),
+    Color32::from_rgb(0, 205, 205),
+    Color32::from_rgb(229, 229, 229),
+    Color32::from_rgb(127, 127, 127),
+    Color32::from_rgb(255, 0, 0),
+    Color32::from_rgb(0, 255, 0),
+    Color32::from_rgb(255, 255, 0),
+    Color32::from_rgb(92, 92, 255),
+    Color32::from_rgb(255, 0, 255),
+    Color32::from_rgb(0, 255, 255),
+    Color32::from_rgb(255, 255, 255),
+];
+
+const CUBE_START: u8 = 16;
+const GRAY_START: u8 = 232;
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Gets the color of a palette index.
+#[must_use]
+pub const fn from_indexed(index: u8) -> Color32 {
+    if index < CUBE_START {
+        STANDARD[index as usize]
+    } else if index < GRAY_START {
+        let i = index - CUBE_START;
+        Color32::from_rgb(
+            CUBE_STEPS[(i / 36) as usize],
+            CUBE_STEPS[(i / 6 % 6) as usize],
+            CUBE_STEPS[(i % 6) as usize],
+        )
+    } else {
+        let level = 8 + (index - GRAY_START) * 10;
+        Color32::from_rgb(level, level, level)
+    }
+}
+
+/// Finds the palette index closest to a color, out of the color cube and grayscale ramp.
+///
+/// The standard colors are never picked, since terminals often theme them.
+#[must_use]
+pub fn to_indexed(color: Color32) -> u8 {
+    let [r, g, b, _] = color.to_array();
+    let cube_step = |value: u8| {
+        (0..CUBE_STEPS.len())
+            .min_by_key(|&i| CUBE_STEPS[i].abs_diff(value))
+            .expect("steps should not be empty") as u8
+    };
+    let cube = CUBE_START + 36 * cube_step(r) + 6 * cube_step(g) + cube_step(b);
+
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray = GRAY_START + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(color, from_indexed(gray)) < distance(color, from_indexed(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn distance(a: Color32, b: Color32) -> u32 {
+    let d = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    d(a.r(), b.r()) + d(a.g(), b.g()) + d(a.b(), b.b())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_indexed, to_indexed};
+    use crate::Color32;
+
+    #[test]
+    fn round_trip() {
+        for index in 16..=255 {
+            assert_eq!(index, to_indexed(from_indexed(index)));
+        }
+        assert_eq!(196, to_indexed(Color32::RED));
+        assert_eq!(Color32::from_rgb(255, 0, 0), from_indexed(196));
+    }
+}
@@ -0,0 +1,299 @@
+//! Features for displaying text messages in [Bevy](https://bevyengine.org) [`Text`].
+//!
+//! The [`MessagePlugin`] keeps the [`Text`] of each entity with a [`MessageText`] component up
+//! to date with its message, laid out using the [`StyleToSections`] resource.
+
+use std::convert::Infallible;
+
+use bevy_app::{App, Plugin, Update};
+use bevy_asset::Handle;
+use bevy_ecs::{
+    change_detection::{DetectChanges, Ref},
+    component::Component,
+    system::{Query, Res, Resource},
+};
+use bevy_render::color::Color;
+use bevy_text::{Font, Text, TextSection, TextStyle};
+
+use crate::{render::Renderer, Color32, FontFamily, Message, MessageStyle};
+
+/// Defines how to convert a [`MessageStyle`] into a Bevy [`TextStyle`].
+///
+/// Bevy text has no font styles of its own, so bold and italic text is displayed by switching
+/// to a different font handle. If a font for a combination of bold and italic is not set, the
+/// closest available font is used instead. Bevy cannot draw underlines or strikethroughs, so
+/// [`MessageStyle::underline`] and [`MessageStyle::strikethrough`] are ignored.
+///
+/// This is a [`Resource`], used by [`MessagePlugin`] to lay out all [`MessageText`]s.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{bevy::StyleToSections, Color32, IntoMessage, Styleable};
+/// use bevy_render::color::Color;
+///
+/// let style_to_sections = StyleToSections::default();
+/// let msg = "Quest complete: "
+///     .with("The Lost Sword".color(Color32::GOLD).bold());
+///
+/// let text = style_to_sections.to_text(&msg);
+/// assert_eq!(2, text.sections.len());
+/// assert_eq!(Color::rgb_u8(255, 215, 0), text.sections[1].style.color);
+/// ```
+#[derive(Debug, Clone, Resource)]
+pub struct StyleToSections {
+    /// Font used for regular text.
+    pub font: Handle<Font>,
+    /// Font used for bold text.
+    pub bold_font: Option<Handle<Font>>,
+    /// Font used for italic text.
+    pub italic_font: Option<Handle<Font>>,
+    /// Font used for text which is both bold and italic.
+    pub bold_italic_font: Option<Handle<Font>>,
+    /// Font used for text with a [`FontFamily::Monospace`] family hint, regardless of whether it
+    /// is bold or italic. If unset, monospace text uses the same font as other text.
+    pub monospace_font: Option<Handle<Font>>,
+    /// Font size used for text with no [`MessageStyle::size`].
+    pub font_size: f32,
+    /// Color used for text with no [`MessageStyle::color`].
+    pub default_color: Color,
+    /// If set, the color used for bold text with no [`MessageStyle::color`].
+    pub bold_color: Option<Color>,
+}
+
+impl Default for StyleToSections {
+    fn default() -> Self {
+        let TextStyle {
+            font,
+            font_size,
+            color,
+        } = TextStyle::default();
+        Self {
+            font,
+            bold_font: None,
+            italic_font: None,
+            bold_italic_font: None,
+            monospace_font: None,
+            font_size,
+            default_color: color,
+            bold_color: None,
+        }
+    }
+}
+
+impl StyleToSections {
+    /// Converts a [`MessageStyle`] to a [`TextStyle`] using the defaults provided in this struct.
+    pub fn to_style(&self, style: MessageStyle) -> TextStyle {
+        let bold = style.bold == Some(true);
+        let italic = style.italic == Some(true);
+
+        let font = match (bold, italic) {
+            (true, true) => self
+                .bold_italic_font
+                .as_ref()
+                .or(self.bold_font.as_ref())
+                .or(self.italic_font.as_ref()),
+            (true, false) => self.bold_font.as_ref(),
+            (false, true) => self.italic_font.as_ref(),
+            (false, false) => None,
+        };
+        let font = match &self.monospace_font {
+            Some(monospace_font) if style.family == Some(FontFamily::Monospace) => {
+                Some(monospace_font)
+            }
+            _ => font,
+        };
+
+        let default_color = match self.bold_color {
+            Some(bold_color) if bold => bold_color,
+            _ => self.default_color,
+        };
+
+        TextStyle {
+            font: font.unwrap_or(&self.font).clone(),
+            font_size: style
+                .size
+                .map_or(self.font_size, |size| size.resolve(self.font_size)),
+            color: style.color.map_or(default_color, to_color),
+        }
+    }
+
+    /// Converts a hierarchy of [`Message`] nodes to a sequence of [`TextSection`]s.
+    ///
+    /// This uses [`Message::render`] with a [`SectionsRenderer`]. Nodes with no content do not
+    /// create a section.
+    pub fn to_sections(&self, text: &Message) -> Vec<TextSection> {
+        let mut renderer = SectionsRenderer::new(self);
+        text.render(&mut renderer)
+            .unwrap_or_else(|never| match never {});
+        renderer.finish()
+    }
+
+    /// Converts a hierarchy of [`Message`] nodes to a [`Text`] with default alignment.
+    pub fn to_text(&self, text: &Message) -> Text {
+        Text::from_sections(self.to_sections(text))
+    }
+}
+
+/// Renders [`Message`]s to a sequence of [`TextSection`]s, using a [`StyleToSections`].
+#[derive(Debug, Clone)]
+pub struct SectionsRenderer<'a> {
+    format: &'a StyleToSections,
+    sections: Vec<TextSection>,
+}
+
+impl<'a> SectionsRenderer<'a> {
+    /// Creates a renderer with no sections.
+    pub const fn new(format: &'a StyleToSections) -> Self {
+        Self {
+            format,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Gets the sections that have been rendered.
+    pub fn finish(self) -> Vec<TextSection> {
+        self.sections
+    }
+}
+
+impl Renderer for SectionsRenderer<'_> {
+    type Error = Infallible;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Infallible> {
+        if !content.is_empty() {
+            self.sections
+                .push(TextSection::new(content, self.format.to_style(style)));
+        }
+        Ok(())
+    }
+}
+
+fn to_color(color: Color32) -> Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    Color::rgba_u8(r, g, b, a)
+}
+
+/// Component which sets the sections of the entity's [`Text`] from a [`Message`].
+///
+/// When this component is added or changed, the sections are replaced by those created by the
+/// [`StyleToSections`] resource. Other settings of the [`Text`], such as alignment, are kept.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bevy_ecs::system::Commands;
+/// use bevy_text::Text;
+/// use expedition::{bevy::MessageText, Color32, IntoMessage, Styleable};
+///
+/// fn spawn_title(mut commands: Commands) {
+///     commands.spawn((
+///         Text::default(),
+///         MessageText("Welcome, ".with("traveller".color(Color32::LIGHT_BLUE))),
+///     ));
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Component)]
+pub struct MessageText(pub Message);
+
+/// Updates the [`Text`] of all changed [`MessageText`]s, or of all of them if the
+/// [`StyleToSections`] resource has changed.
+pub fn update_message_text(
+    style_to_sections: Res<StyleToSections>,
+    mut query: Query<(Ref<MessageText>, &mut Text)>,
+) {
+    let all = style_to_sections.is_changed();
+    for (message, mut text) in &mut query {
+        if all || message.is_changed() {
+            text.sections = style_to_sections.to_sections(&message.0);
+        }
+    }
+}
+
+/// Plugin which keeps [`MessageText`]s up to date using [`update_message_text`].
+///
+/// This initializes the [`StyleToSections`] resource if it does not already exist.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePlugin;
+
+impl Plugin for MessagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StyleToSections>()
+            .add_systems(Update, update_message_text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use bevy_asset::{Handle, HandleId};
+    use bevy_render::color::Color;
+    use bevy_text::{Font, Text};
+
+    use crate::{Color32, FontFamily, FontSize, IntoMessage, Styleable};
+
+    use super::{MessagePlugin, MessageText, StyleToSections};
+
+    fn font(id: u64) -> Handle<Font> {
+        Handle::weak(HandleId::new(Default::default(), id))
+    }
+
+    #[test]
+    fn fonts() {
+        let style_to_sections = StyleToSections {
+            bold_font: Some(font(1)),
+            italic_font: Some(font(2)),
+            monospace_font: Some(font(3)),
+            ..Default::default()
+        };
+        let msg = "regular"
+            .with("bold".bold().with("both".italic()))
+            .with("code".family(FontFamily::Monospace).bold())
+            .with("big".size(FontSize::Scale(2.0)));
+
+        let sections = style_to_sections.to_sections(&msg);
+        let fonts: Vec<_> = sections.iter().map(|section| &section.style.font).collect();
+        let regular = &style_to_sections.font;
+        // no bold italic font, so falls back to bold
+        assert_eq!(vec![regular, &font(1), &font(1), &font(3), regular], fonts,);
+        assert_eq!(24.0, sections[4].style.font_size);
+    }
+
+    #[test]
+    fn bold_monospace() {
+        let style_to_sections = StyleToSections {
+            bold_font: Some(font(1)),
+            ..Default::default()
+        };
+        let msg = "code".family(FontFamily::Monospace).bold();
+
+        // no monospace font, so falls back to bold
+        let sections = style_to_sections.to_sections(&msg);
+        assert_eq!(font(1), sections[0].style.font);
+    }
+
+    #[test]
+    fn plugin() {
+        let mut app = App::new();
+        app.add_plugins(MessagePlugin);
+        let entity = app
+            .world
+            .spawn((Text::default(), MessageText("a".color(Color32::RED))))
+            .id();
+
+        app.update();
+        let text = app.world.get::<Text>(entity).unwrap();
+        assert_eq!("a", text.sections[0].value);
+        assert_eq!(Color::rgb_u8(255, 0, 0), text.sections[0].style.color);
+
+        app.world.get_mut::<MessageText>(entity).unwrap().0 = "b".with("c".bold());
+        app.update();
+        let text = app.world.get::<Text>(entity).unwrap();
+        assert_eq!(2, text.sections.len());
+
+        app.world.resource_mut::<StyleToSections>().font_size = 30.0;
+        app.update();
+        let text = app.world.get::<Text>(entity).unwrap();
+        assert_eq!(30.0, text.sections[0].style.font_size);
+    }
+}
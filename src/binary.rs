@@ -0,0 +1,531 @@
+//! Compact binary encoding of [`Message`]s, designed for sending messages over a network.
+//!
+//! The format is a depth-first sequence of nodes, where each node is written as:
+//! - the content length as a [LEB128] varint, followed by the UTF-8 content bytes
+//! - a style header byte, where each bit marks a [`MessageStyle`] field as present
+//! - if any decoration is present, a byte holding the values of the present decorations
+//! - if a color is present, the 4 bytes of the [`Color32`] in premultiplied RGBA order
+//! - if a size is present, the 4 bytes of the little-endian `f32`, with the header marking if
+//!   the size is a [`FontSize::Scale`]
+//! - if a family is present, a byte identifying the [`FontFamily`]
+//! - the number of children as a varint, followed by each child node
+//!
+//! An unstyled node with no content and no children therefore takes up only 3 bytes.
+//!
+//! Since payloads may come from untrusted sources, decoding is always bounded by a set of
+//! [`DecodeLimits`], which reject payloads that are too large or too deeply nested before any
+//! work is done on them.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{Color32, IntoMessage, Message, Styleable};
+//!
+//! let msg = "Hello, "
+//!     .with("world!".color(Color32::RED).bold());
+//!
+//! let bytes = msg.to_bytes();
+//! assert_eq!(msg, Message::from_bytes(&bytes).unwrap());
+//! ```
+//!
+//! [LEB128]: https://en.wikipedia.org/wiki/LEB128
+
+use std::{error::Error, fmt, str};
+
+use crate::{Color32, FontFamily, FontSize, Message, MessageStyle};
+
+const STYLE_COLOR: u8 = 1 << 0;
+const STYLE_BOLD: u8 = 1 << 1;
+const STYLE_ITALIC: u8 = 1 << 2;
+const STYLE_UNDERLINE: u8 = 1 << 3;
+const STYLE_STRIKETHROUGH: u8 = 1 << 4;
+const STYLE_SIZE: u8 = 1 << 5;
+const STYLE_FAMILY: u8 = 1 << 6;
+/// Only valid if [`STYLE_SIZE`] is set.
+const STYLE_SIZE_SCALE: u8 = 1 << 7;
+
+const STYLE_DECORATIONS: u8 = STYLE_BOLD | STYLE_ITALIC | STYLE_UNDERLINE | STYLE_STRIKETHROUGH;
+
+const FAMILY_PROPORTIONAL: u8 = 0;
+const FAMILY_MONOSPACE: u8 = 1;
+
+/// Maximum number of bytes that a varint encoding a `u64` can take up.
+const MAX_VARINT_LEN: usize = 10;
+
+impl Message {
+    /// Appends the binary encoding of this message to the end of `buf`.
+    ///
+    /// See the [module-level documentation](crate::binary) for a description of the format.
+    ///
+    /// The hierarchy is traversed using an explicit stack instead of recursion, so messages of
+    /// any depth can be encoded.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            write_varint(buf, node.content.len() as u64);
+            buf.extend_from_slice(node.content.as_bytes());
+            encode_style(buf, node.style);
+
+            write_varint(buf, node.children.len() as u64);
+            stack.extend(node.children.iter().rev());
+        }
+    }
+
+    /// Encodes this message into a new byte buffer.
+    ///
+    /// See [`Message::encode`].
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode(&mut buf);
+        buf
+    }
+
+    /// Decodes a message from its binary encoding, using the default [`DecodeLimits`].
+    ///
+    /// The entire buffer must be consumed by the message, otherwise this returns
+    /// [`DecodeError::TrailingBytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        DecodeLimits::default().decode(bytes)
+    }
+}
+
+/// Bounds applied when decoding a [`Message`] from an untrusted payload.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{binary::{DecodeError, DecodeLimits}, IntoMessage};
+///
+/// let limits = DecodeLimits {
+///     max_depth: 2,
+///     ..Default::default()
+/// };
+///
+/// let shallow = "one".with("two");
+/// assert_eq!(Ok(shallow.clone()), limits.decode(&shallow.to_bytes()));
+///
+/// let deep = "one".with("two".with("three"));
+/// assert_eq!(Err(DecodeError::TooDeep { max: 2 }), limits.decode(&deep.to_bytes()));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodeLimits {
+    /// Maximum length of the whole payload, in bytes.
+    pub max_len: usize,
+    /// Maximum number of nested message nodes, including the root node.
+    pub max_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_len: 64 * 1024,
+            max_depth: 32,
+        }
+    }
+}
+
+impl DecodeLimits {
+    /// Decodes a message from its binary encoding, rejecting it if it does not fit within these
+    /// limits.
+    ///
+    /// The entire buffer must be consumed by the message, otherwise this returns
+    /// [`DecodeError::TrailingBytes`].
+    pub fn decode(&self, bytes: &[u8]) -> Result<Message, DecodeError> {
+        if bytes.len() > self.max_len {
+            return Err(DecodeError::TooLarge {
+                len: bytes.len(),
+                max: self.max_len,
+            });
+        }
+
+        let mut reader = Reader {
+            bytes,
+            max_depth: self.max_depth,
+        };
+        let message = reader.message()?;
+        if reader.bytes.is_empty() {
+            Ok(message)
+        } else {
+            Err(DecodeError::TrailingBytes)
+        }
+    }
+}
+
+/// An error that occurs when decoding a [`Message`] from its binary encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The payload ended before a full message was read.
+    UnexpectedEof,
+    /// The payload was longer than [`DecodeLimits::max_len`].
+    TooLarge {
+        /// Length of the payload.
+        len: usize,
+        /// Maximum length allowed.
+        max: usize,
+    },
+    /// The message nodes were nested deeper than [`DecodeLimits::max_depth`].
+    TooDeep {
+        /// Maximum depth allowed.
+        max: usize,
+    },
+    /// A varint was longer than the maximum length of an encoded `u64`.
+    InvalidVarint,
+    /// The content of a node was not valid UTF-8.
+    InvalidUtf8,
+    /// A style header contained an invalid combination of bits.
+    InvalidStyle(u8),
+    /// A style contained a font family which does not exist.
+    InvalidFontFamily(u8),
+    /// There were bytes left over after the message was read.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => write!(f, "unexpected end of payload"),
+            Self::TooLarge { len, max } => {
+                write!(f, "payload is {} bytes long, maximum is {}", len, max)
+            }
+            Self::TooDeep { max } => write!(f, "message is nested deeper than {} nodes", max),
+            Self::InvalidVarint => write!(f, "invalid varint"),
+            Self::InvalidUtf8 => write!(f, "content is not valid UTF-8"),
+            Self::InvalidStyle(header) => write!(f, "invalid style header {:#010b}", header),
+            Self::InvalidFontFamily(family) => write!(f, "invalid font family {}", family),
+            Self::TrailingBytes => write!(f, "trailing bytes after message"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+// encoding
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn encode_style(buf: &mut Vec<u8>, style: MessageStyle) {
+    let decorations = [
+        (STYLE_BOLD, style.bold),
+        (STYLE_ITALIC, style.italic),
+        (STYLE_UNDERLINE, style.underline),
+        (STYLE_STRIKETHROUGH, style.strikethrough),
+    ];
+
+    let mut header = 0;
+    let mut values = 0;
+    if style.color.is_some() {
+        header |= STYLE_COLOR;
+    }
+    for (bit, state) in decorations {
+        if let Some(value) = state {
+            header |= bit;
+            if value {
+                values |= bit;
+            }
+        }
+    }
+
+    if style.size.is_some() {
+        header |= STYLE_SIZE;
+    }
+    if let Some(FontSize::Scale(_)) = style.size {
+        header |= STYLE_SIZE_SCALE;
+    }
+    if style.family.is_some() {
+        header |= STYLE_FAMILY;
+    }
+
+    buf.push(header);
+    if header & STYLE_DECORATIONS != 0 {
+        buf.push(values);
+    }
+    if let Some(color) = style.color {
+        buf.extend_from_slice(&color.to_array());
+    }
+    if let Some(FontSize::Points(size) | FontSize::Scale(size)) = style.size {
+        buf.extend_from_slice(&size.to_le_bytes());
+    }
+    if let Some(family) = style.family {
+        buf.push(match family {
+            FontFamily::Proportional => FAMILY_PROPORTIONAL,
+            FontFamily::Monospace => FAMILY_MONOSPACE,
+        });
+    }
+}
+
+// decoding
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    max_depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("should have taken N bytes"))
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+        for i in 0..MAX_VARINT_LEN {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidVarint)
+    }
+
+    /// Reads a varint used as a length, which can never be longer than the remaining payload.
+    fn len(&mut self) -> Result<usize, DecodeError> {
+        let len = self.varint()?;
+        match usize::try_from(len) {
+            Ok(len) if len <= self.bytes.len() => Ok(len),
+            _ => Err(DecodeError::UnexpectedEof),
+        }
+    }
+
+    fn style(&mut self) -> Result<MessageStyle, DecodeError> {
+        let header = self.byte()?;
+        if header & STYLE_SIZE_SCALE != 0 && header & STYLE_SIZE == 0 {
+            return Err(DecodeError::InvalidStyle(header));
+        }
+
+        let values = if header & STYLE_DECORATIONS == 0 {
+            0
+        } else {
+            self.byte()?
+        };
+        let decoration = |bit: u8| (header & bit != 0).then_some(values & bit != 0);
+
+        let color = if header & STYLE_COLOR == 0 {
+            None
+        } else {
+            let [r, g, b, a] = self.array()?;
+            Some(Color32::from_rgba_premultiplied(r, g, b, a))
+        };
+
+        let size = if header & STYLE_SIZE == 0 {
+            None
+        } else {
+            let size = f32::from_le_bytes(self.array()?);
+            Some(if header & STYLE_SIZE_SCALE == 0 {
+                FontSize::Points(size)
+            } else {
+                FontSize::Scale(size)
+            })
+        };
+
+        let family = if header & STYLE_FAMILY == 0 {
+            None
+        } else {
+            Some(match self.byte()? {
+                FAMILY_PROPORTIONAL => FontFamily::Proportional,
+                FAMILY_MONOSPACE => FontFamily::Monospace,
+                family => return Err(DecodeError::InvalidFontFamily(family)),
+            })
+        };
+
+        Ok(MessageStyle {
+            color,
+            bold: decoration(STYLE_BOLD),
+            italic: decoration(STYLE_ITALIC),
+            underline: decoration(STYLE_UNDERLINE),
+            strikethrough: decoration(STYLE_STRIKETHROUGH),
+            size,
+            family,
+        })
+    }
+
+    /// Reads a node's content and style, and the number of children which follow it.
+    fn node(&mut self) -> Result<(Message, usize), DecodeError> {
+        let content_len = self.len()?;
+        let content = str::from_utf8(self.take(content_len)?)
+            .map_err(|_| DecodeError::InvalidUtf8)?
+            .to_owned();
+        let style = self.style()?;
+
+        // every child takes up at least 3 bytes, so this can't allocate more than the payload
+        let children_len = self.len()?;
+        let node = Message {
+            content,
+            style,
+            children: Vec::with_capacity(children_len / 3),
+        };
+        Ok((node, children_len))
+    }
+
+    /// Reads a whole message, using an explicit stack of the nodes whose children are still
+    /// being read, so that the depth is only bounded by [`DecodeLimits::max_depth`].
+    fn message(&mut self) -> Result<Message, DecodeError> {
+        let mut stack: Vec<(Message, usize)> = Vec::new();
+        loop {
+            if stack.len() >= self.max_depth {
+                return Err(DecodeError::TooDeep {
+                    max: self.max_depth,
+                });
+            }
+
+            let (node, remaining) = self.node()?;
+            if remaining > 0 {
+                stack.push((node, remaining));
+                continue;
+            }
+
+            // attach every node which has all of its children to its parent
+            let mut done = node;
+            loop {
+                let Some((parent, parent_remaining)) = stack.last_mut() else {
+                    return Ok(done);
+                };
+                parent.children.push(done);
+                *parent_remaining -= 1;
+                if *parent_remaining > 0 {
+                    break;
+                }
+                done = stack.pop().expect("parent should be on the stack").0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color32, FontFamily, FontSize, IntoMessage, Message, Styleable};
+
+    use super::{DecodeError, DecodeLimits};
+
+    #[test]
+    fn round_trip() {
+        let msg = "Unstyled, "
+            .with("Red ".color(Color32::RED).with("and some bold ".bold()))
+            .with(
+                "Blue "
+                    .color(Color32::BLUE)
+                    .with("and not italic ".no_italic()),
+            )
+            .with(
+                "all the decorations"
+                    .bold()
+                    .italic()
+                    .underline()
+                    .strikethrough(),
+            )
+            .with("big ".size(FontSize::Scale(2.0)))
+            .with(
+                "code"
+                    .size(FontSize::Points(10.0))
+                    .family(FontFamily::Monospace),
+            )
+            .with(Message::new("ü".repeat(200)));
+
+        assert_eq!(Ok(msg.clone()), Message::from_bytes(&msg.to_bytes()));
+    }
+
+    #[test]
+    fn compact() {
+        assert_eq!(vec![0, 0, 0], Message::default().to_bytes());
+        assert_eq!(
+            vec![1, b'a', 0b0000_0011, 0b0000_0010, 255, 0, 0, 255, 0],
+            "a".color(Color32::RED).bold().to_bytes(),
+        );
+    }
+
+    #[test]
+    fn too_large() {
+        let limits = DecodeLimits {
+            max_len: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(DecodeError::TooLarge { len: 5, max: 4 }),
+            limits.decode(&Message::new("ab").to_bytes()),
+        );
+    }
+
+    #[test]
+    fn too_deep() {
+        let mut msg = Message::new("leaf");
+        for _ in 0..100 {
+            msg = Message::default().with(msg);
+        }
+        assert_eq!(
+            Err(DecodeError::TooDeep { max: 32 }),
+            Message::from_bytes(&msg.to_bytes()),
+        );
+    }
+
+    #[test]
+    fn deep() {
+        let depth = 100_000;
+        let mut msg = Message::new("leaf");
+        for _ in 0..depth {
+            msg = Message::default().with(msg);
+        }
+        let bytes = msg.to_bytes();
+
+        let limits = DecodeLimits {
+            max_len: usize::MAX,
+            max_depth: usize::MAX,
+        };
+        let decoded = limits.decode(&bytes).unwrap();
+        let mut node = &decoded;
+        for _ in 0..depth {
+            node = &node.children[0];
+        }
+        assert_eq!("leaf", node.content);
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(Err(DecodeError::UnexpectedEof), Message::from_bytes(&[]));
+        assert_eq!(
+            Err(DecodeError::UnexpectedEof),
+            Message::from_bytes(&[5, b'a', 0, 0])
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidUtf8),
+            Message::from_bytes(&[1, 0xff, 0, 0])
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidStyle(0x80)),
+            Message::from_bytes(&[0, 0x80, 0])
+        );
+        assert_eq!(
+            Err(DecodeError::TrailingBytes),
+            Message::from_bytes(&[0, 0, 0, 0])
+        );
+        assert_eq!(
+            Err(DecodeError::InvalidVarint),
+            Message::from_bytes(&[0xff; 16])
+        );
+        // claims a huge number of children, but the payload can't hold them
+        assert_eq!(
+            Err(DecodeError::UnexpectedEof),
+            Message::from_bytes(&[0, 0, 0xff, 0xff, 0xff, 0xff, 0x0f]),
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! A bounded in-memory log of [`Message`]s, for display in e.g. an in-game developer console.
+//!
+//! With the `egui` feature, a [`MessageLog`] can be shown using `egui::ConsoleView`. With the
+//! `tracing` feature, events can be sent to a shared log through a `tracing::MessageLayer`.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{console::{MessageLog, Severity}, Color32, IntoMessage, Styleable};
+//!
+//! let mut log = MessageLog::new(2);
+//! log.push(Severity::Info, "Loading level...");
+//! log.push(Severity::Warn, "Missing texture: ".with("grass.png".italic()));
+//! log.push(Severity::Info, "Level loaded");
+//!
+//! // the oldest entry was removed to stay within capacity
+//! let lines: Vec<_> = log.iter().map(|entry| entry.message.to_string()).collect();
+//! assert_eq!(vec!["Missing texture: grass.png", "Level loaded"], lines);
+//! ```
+
+use std::{
+    collections::{vec_deque, VecDeque},
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{Color32, Message, MessageStyle, Styleable};
+
+/// How important an entry in a [`MessageLog`] is.
+///
+/// Severities are ordered from least to most important.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Very verbose diagnostic information.
+    Trace,
+    /// Diagnostic information.
+    Debug,
+    /// General information.
+    #[default]
+    Info,
+    /// Something unexpected which can be recovered from.
+    Warn,
+    /// Something which failed.
+    Error,
+}
+
+impl Severity {
+    /// All severities, from least to most important.
+    pub const ALL: [Self; 5] = [
+        Self::Trace,
+        Self::Debug,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+    ];
+
+    /// Gets the upper case name of this severity.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Trace => "TRACE",
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+
+    /// Gets the default style of the name of this severity, used for the levels of formatted
+    /// `tracing` events and `log` records.
+    #[must_use]
+    pub fn style(self) -> MessageStyle {
+        let color = match self {
+            Self::Trace => Color32::from_rgb(200, 120, 255),
+            Self::Debug => Color32::LIGHT_BLUE,
+            Self::Info => Color32::GREEN,
+            Self::Warn => Color32::YELLOW,
+            Self::Error => Color32::RED,
+        };
+        MessageStyle::new().color(color)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An entry in a [`MessageLog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// When the entry was added.
+    pub time: SystemTime,
+    /// How important the entry is.
+    pub severity: Severity,
+    /// Content of the entry.
+    pub message: Message,
+}
+
+/// Ring buffer of the most recent [`LogEntry`]s.
+///
+/// Once the log holds [`MessageLog::capacity`] entries, adding an entry removes the oldest one.
+#[derive(Debug, Clone)]
+pub struct MessageLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    pushed: u64,
+}
+
+impl MessageLog {
+    /// Creates an empty log which holds at most `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be greater than 0");
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            pushed: 0,
+        }
+    }
+
+    /// Adds a message with the current time.
+    pub fn push(&mut self, severity: Severity, message: impl Into<Message>) {
+        self.push_entry(LogEntry {
+            time: SystemTime::now(),
+            severity,
+            message: message.into(),
+        });
+    }
+
+    /// Adds an entry, removing the oldest entry if the log is full.
+    pub fn push_entry(&mut self, entry: LogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.pushed += 1;
+    }
+
+    /// Maximum number of entries in the log.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries in the log.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the log has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total number of entries ever added to the log, including those which have since been
+    /// removed.
+    ///
+    /// The entry at index `i` was the `total_pushed() - len() + i`th entry added, which can be
+    /// used to track entries across additions.
+    #[must_use]
+    pub const fn total_pushed(&self) -> u64 {
+        self.pushed
+    }
+
+    /// Gets the entry at `index`, where 0 is the oldest entry.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&LogEntry> {
+        self.entries.get(index)
+    }
+
+    /// Iterates over the entries from oldest to newest.
+    pub fn iter(&self) -> vec_deque::Iter<'_, LogEntry> {
+        self.entries.iter()
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<'a> IntoIterator for &'a MessageLog {
+    type Item = &'a LogEntry;
+    type IntoIter = vec_deque::Iter<'a, LogEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Formats the time of day of `time` in UTC, as `HH:MM:SS.mmm`.
+///
+/// This is the format used for the times of entries in `egui::ConsoleView`.
+#[must_use]
+pub fn utc_time(time: SystemTime) -> String {
+    let millis = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    let day_millis = millis % (24 * 60 * 60 * 1000);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        day_millis / (60 * 60 * 1000),
+        day_millis / (60 * 1000) % 60,
+        day_millis / 1000 % 60,
+        day_millis % 1000,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{utc_time, MessageLog, Severity};
+
+    #[test]
+    fn ring_buffer() {
+        let mut log = MessageLog::new(3);
+        for i in 0..5 {
+            log.push(Severity::Info, i.to_string());
+        }
+        assert_eq!(3, log.len());
+        assert_eq!(5, log.total_pushed());
+        let lines: Vec<_> = log.iter().map(|entry| entry.message.to_string()).collect();
+        assert_eq!(vec!["2", "3", "4"], lines);
+
+        log.clear();
+        assert!(log.is_empty());
+        assert_eq!(5, log.total_pushed());
+    }
+
+    #[test]
+    fn time() {
+        let time = UNIX_EPOCH + Duration::from_millis(((3 * 24 + 13) * 60 + 7) * 60_000 + 5_042);
+        assert_eq!("13:07:05.042", utc_time(time));
+    }
+}
@@ -0,0 +1,168 @@
+//! Features for converting text messages to a [`cosmic_text`] attributes list, used in custom
+//! text renderers.
+
+use std::convert::Infallible;
+
+use cosmic_text::{Attrs, AttrsList, Color, Family, Style, Weight};
+
+use crate::{render::Renderer, FontFamily, Message, MessageStyle};
+
+/// Defines how to convert a [`MessageStyle`] into cosmic-text [`Attrs`].
+///
+/// Bold text is converted to [`Weight::BOLD`] and italic text to [`Style::Italic`], so the font
+/// system picks the matching faces of the font family. cosmic-text does not draw underlines or
+/// strikethroughs, and does not support per-span font sizes, so [`MessageStyle::underline`],
+/// [`MessageStyle::strikethrough`] and [`MessageStyle::size`] are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{cosmic_text::StyleToAttrs, IntoMessage, Styleable};
+/// use cosmic_text::Weight;
+///
+/// let msg = "Press ".with("Enter".bold()).with(" to continue");
+///
+/// let (text, attrs_list) = StyleToAttrs::default().to_attrs_list(&msg);
+/// assert_eq!("Press Enter to continue", text);
+/// assert_eq!(Weight::BOLD, attrs_list.get_span(6).weight);
+/// assert_eq!(Weight::NORMAL, attrs_list.get_span(0).weight);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StyleToAttrs<'a> {
+    /// Attributes of text with no style, which are also the defaults of the created
+    /// [`AttrsList`].
+    pub defaults: Attrs<'a>,
+}
+
+impl Default for StyleToAttrs<'_> {
+    fn default() -> Self {
+        Self {
+            defaults: Attrs::new(),
+        }
+    }
+}
+
+impl<'a> StyleToAttrs<'a> {
+    /// Converts a [`MessageStyle`] to [`Attrs`] using the defaults provided in this struct.
+    pub fn to_attrs(&self, style: MessageStyle) -> Attrs<'a> {
+        let mut attrs = self.defaults;
+        if let Some(color) = style.color {
+            let [r, g, b, a] = color.to_srgba_unmultiplied();
+            attrs.color_opt = Some(Color::rgba(r, g, b, a));
+        }
+        match style.bold {
+            Some(true) => attrs.weight = Weight::BOLD,
+            Some(false) => attrs.weight = Weight::NORMAL,
+            None => {}
+        }
+        match style.italic {
+            Some(true) => attrs.style = Style::Italic,
+            Some(false) => attrs.style = Style::Normal,
+            None => {}
+        }
+        match style.family {
+            Some(FontFamily::Monospace) => attrs.family = Family::Monospace,
+            Some(FontFamily::Proportional) if attrs.family == Family::Monospace => {
+                attrs.family = Family::SansSerif;
+            }
+            _ => {}
+        }
+        attrs
+    }
+
+    /// Converts a hierarchy of [`Message`] nodes to its plain text, and the [`AttrsList`] of
+    /// byte ranges in that text.
+    ///
+    /// This uses [`Message::render`] with an [`AttrsListRenderer`]. Spans which have the same
+    /// attributes as [`StyleToAttrs::defaults`] are not added.
+    ///
+    /// cosmic-text lays out text line by line, so if the text contains newlines, the list
+    /// should be split into one list per line using [`AttrsList::split_off`].
+    pub fn to_attrs_list(&self, text: &Message) -> (String, AttrsList) {
+        let mut renderer = AttrsListRenderer::new(self);
+        text.render(&mut renderer)
+            .unwrap_or_else(|never| match never {});
+        renderer.finish()
+    }
+}
+
+/// Renders [`Message`]s to plain text and an [`AttrsList`], using a [`StyleToAttrs`].
+///
+/// Rendering multiple messages appends them all to the same text.
+#[derive(Debug, Clone)]
+pub struct AttrsListRenderer<'a, 'b> {
+    format: &'b StyleToAttrs<'a>,
+    text: String,
+    attrs_list: AttrsList,
+}
+
+impl<'a, 'b> AttrsListRenderer<'a, 'b> {
+    /// Creates a renderer with no text.
+    pub fn new(format: &'b StyleToAttrs<'a>) -> Self {
+        Self {
+            format,
+            text: String::new(),
+            attrs_list: AttrsList::new(format.defaults),
+        }
+    }
+
+    /// Gets the text and attributes that have been rendered.
+    pub fn finish(self) -> (String, AttrsList) {
+        (self.text, self.attrs_list)
+    }
+}
+
+impl Renderer for AttrsListRenderer<'_, '_> {
+    type Error = Infallible;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Infallible> {
+        let start = self.text.len();
+        self.text.push_str(content);
+        let attrs = self.format.to_attrs(style);
+        if attrs != self.format.defaults {
+            self.attrs_list.add_span(start..self.text.len(), attrs);
+        }
+        Ok(())
+    }
+}
+
+impl Message {
+    /// Converts this message to its plain text and a cosmic-text [`AttrsList`], using the
+    /// default [`StyleToAttrs`].
+    pub fn to_attrs_list(&self) -> (String, AttrsList) {
+        StyleToAttrs::default().to_attrs_list(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Color, Family, Style, Weight};
+
+    use crate::{Color32, FontFamily, IntoMessage, Styleable};
+
+    use super::StyleToAttrs;
+
+    #[test]
+    fn spans() {
+        let style_to_attrs = StyleToAttrs {
+            defaults: Attrs::new().family(Family::Monospace),
+        };
+        let msg = "ab"
+            .color(Color32::RED)
+            .with("cd".bold().italic())
+            .with("ef".family(FontFamily::Proportional).with_bold(Some(false)));
+
+        let (text, attrs_list) = style_to_attrs.to_attrs_list(&msg);
+        assert_eq!("abcdef", text);
+
+        let red = Attrs::new()
+            .family(Family::Monospace)
+            .color(Color::rgb(255, 0, 0));
+        assert_eq!(red, attrs_list.get_span(1));
+        assert_eq!(
+            red.weight(Weight::BOLD).style(Style::Italic),
+            attrs_list.get_span(2),
+        );
+        assert_eq!(red.family(Family::SansSerif), attrs_list.get_span(4));
+    }
+}
@@ -0,0 +1,293 @@
+//! Features for writing out text messages as text using terminal commands, using [`crossterm`].
+
+use std::io::{self, Write};
+
+use crossterm::{
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
+    QueueableCommand,
+};
+
+use crate::{
+    ansi::{self, ColorMode},
+    render::Renderer,
+    Color32, Message, MessageStyle,
+};
+
+/// Writes [`Message`]s to a [`Write`] by queueing crossterm commands.
+///
+/// The writer keeps track of the color and attributes that it has set on the terminal, and only
+/// queues commands for what changes between segments. Attributes which were set by an earlier
+/// segment are explicitly turned off when a later segment does not use them, and everything is
+/// reset at the end of each message.
+///
+/// Commands are only queued, so the underlying writer must be flushed for them to be
+/// executed.
+///
+/// [`MessageStyle::size`] and [`MessageStyle::family`] are ignored, as described in
+/// [`FontSize`](crate::FontSize).
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use expedition::{crossterm::CrosstermWriter, Color32, IntoMessage, Styleable};
+///
+/// let msg = "Build "
+///     .with("failed".color(Color32::RED).bold())
+///     .with(": 3 errors");
+///
+/// let mut writer = CrosstermWriter::new(std::io::stdout());
+/// writer.write(&msg)?;
+/// writeln!(writer.get_mut())?;
+/// writer.get_mut().flush()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct CrosstermWriter<W> {
+    writer: W,
+    colors: ColorMode,
+    /// Style set on the terminal, or [`None`] if it is unknown because queueing a command failed
+    /// part way through changing it.
+    state: Option<TerminalState>,
+}
+
+/// Style currently set on the terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct TerminalState {
+    color: Option<Color32>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl TerminalState {
+    fn from_style(style: MessageStyle) -> Self {
+        Self {
+            color: style.color,
+            bold: style.bold == Some(true),
+            italic: style.italic == Some(true),
+            underline: style.underline == Some(true),
+            strikethrough: style.strikethrough == Some(true),
+        }
+    }
+}
+
+impl<W: Write> CrosstermWriter<W> {
+    /// Creates a writer which writes to `writer`, which is assumed to have the terminal's
+    /// default style.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            colors: ColorMode::default(),
+            state: Some(TerminalState::default()),
+        }
+    }
+
+    /// Sets how colors are converted to terminal colors.
+    #[must_use]
+    pub const fn colors(mut self, colors: ColorMode) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Queues the commands to write `message`, then resets the terminal's style.
+    ///
+    /// This uses [`Message::render`], so the style is reset even if queueing a command fails
+    /// part way through.
+    ///
+    /// # Errors
+    ///
+    /// Errors if queueing a command fails. The first error stops any further output.
+    pub fn write(&mut self, message: &Message) -> io::Result<()> {
+        message.render(self)
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub const fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing styling commands directly to this writer will desync the style that this writer
+    /// believes the terminal has.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Unwraps this writer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Renderer for CrosstermWriter<W> {
+    type Error = io::Error;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> io::Result<()> {
+        if content.is_empty() {
+            return Ok(());
+        }
+        let next = TerminalState::from_style(style);
+        // until every command is queued, the terminal may have any mix of both styles
+        let current = match self.state.take() {
+            Some(current) => current,
+            None => {
+                self.writer.queue(SetAttribute(Attribute::Reset))?;
+                TerminalState::default()
+            }
+        };
+
+        if next.color != current.color {
+            let color = next.color.map_or(Color::Reset, |color| match self.colors {
+                ColorMode::Rgb => Color::Rgb {
+                    r: color.r(),
+                    g: color.g(),
+                    b: color.b(),
+                },
+                ColorMode::Indexed => Color::AnsiValue(ansi::to_indexed(color)),
+            });
+            self.writer.queue(SetForegroundColor(color))?;
+        }
+
+        let attributes = [
+            (
+                current.bold,
+                next.bold,
+                Attribute::Bold,
+                Attribute::NormalIntensity,
+            ),
+            (
+                current.italic,
+                next.italic,
+                Attribute::Italic,
+                Attribute::NoItalic,
+            ),
+            (
+                current.underline,
+                next.underline,
+                Attribute::Underlined,
+                Attribute::NoUnderline,
+            ),
+            (
+                current.strikethrough,
+                next.strikethrough,
+                Attribute::CrossedOut,
+                Attribute::NotCrossedOut,
+            ),
+        ];
+        for (was, is, on, off) in attributes {
+            if was != is {
+                self.writer.queue(SetAttribute(if is { on } else { off }))?;
+            }
+        }
+
+        self.state = Some(next);
+        self.writer.queue(Print(content))?;
+        Ok(())
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        if self.state != Some(TerminalState::default()) {
+            // resetting the attributes also resets the color
+            self.writer.queue(SetAttribute(Attribute::Reset))?;
+            self.state = Some(TerminalState::default());
+        }
+        Ok(())
+    }
+}
+
+impl Message {
+    /// Writes this text message as a styled message to `writer` using crossterm commands.
+    ///
+    /// See [`CrosstermWriter`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Errors if queueing a command fails.
+    pub fn write_crossterm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        CrosstermWriter::new(writer).write(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use crate::{ansi::ColorMode, Color32, IntoMessage, Message, Styleable};
+
+    use super::CrosstermWriter;
+
+    fn write(mut writer: CrosstermWriter<Vec<u8>>, msg: &Message) -> String {
+        writer.write(msg).unwrap();
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn turns_off_attributes() {
+        let msg = "a"
+            .bold()
+            .with("b".italic())
+            .with("c".with_bold(Some(false)))
+            .with("d");
+        assert_eq!(
+            "\x1b[1ma\x1b[3mb\x1b[22m\x1b[23mc\x1b[1md\x1b[0m",
+            write(CrosstermWriter::new(Vec::new()), &msg),
+        );
+    }
+
+    #[test]
+    fn colors() {
+        let msg = "a".color(Color32::RED).with("b".into_text()).with(
+            "c".color(Color32::BLUE)
+                .with("d".color(Color32::from_rgb(0, 0, 255))),
+        );
+        let rgb = write(CrosstermWriter::new(Vec::new()), &msg);
+        assert_eq!("\x1b[38;2;255;0;0mab\x1b[38;2;0;0;255mcd\x1b[0m", rgb,);
+
+        let msg = "a".color(Color32::RED).with("b".color(Color32::BLACK));
+        let indexed = write(
+            CrosstermWriter::new(Vec::new()).colors(ColorMode::Indexed),
+            &msg,
+        );
+        assert_eq!("\x1b[38;5;196ma\x1b[38;5;16mb\x1b[0m", indexed);
+    }
+
+    #[test]
+    fn reset_after_error() {
+        /// Fails the first write after the color has been set.
+        #[derive(Default)]
+        struct Failing {
+            out: Vec<u8>,
+            failed: bool,
+        }
+
+        impl Write for Failing {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if !self.failed && self.out.ends_with(b"\x1b[38;2;255;0;0m") {
+                    self.failed = true;
+                    return Err(io::Error::new(io::ErrorKind::Other, "failed"));
+                }
+                self.out.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = CrosstermWriter::new(Failing::default());
+        assert!(writer.write(&"a".color(Color32::RED).bold()).is_err());
+        assert_eq!(
+            "\x1b[38;2;255;0;0m\x1b[0m",
+            String::from_utf8(writer.into_inner().out).unwrap(),
+        );
+    }
+
+    #[test]
+    fn unstyled() {
+        let msg = "plain".with(" text");
+        assert_eq!("plain text", write(CrosstermWriter::new(Vec::new()), &msg));
+    }
+}
@@ -1,8 +1,30 @@
 //! Features for converting objects to an [`egui`] format.
 
-use egui::{text::LayoutJob, Align, Color32, FontId, Stroke, TextFormat};
+use std::{
+    collections::{
+        hash_map::{self, DefaultHasher},
+        BTreeMap, HashMap, VecDeque,
+    },
+    convert::Infallible,
+    fmt::Write,
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::Arc,
+};
 
-use crate::{Message, MessageStyle, StackFlattener};
+use egui::{
+    color_picker::{self, Alpha},
+    pos2,
+    text::{Fonts, LayoutJob},
+    Align, Color32, ComboBox, CursorIcon, Event, FontId, Galley, Label, Painter, Pos2, Rect,
+    Response, RichText, ScrollArea, Sense, Stroke, TextEdit, TextFormat, Ui, Vec2, Widget,
+};
+
+use crate::{
+    console::{utc_time, LogEntry, MessageLog, Severity},
+    render::Renderer,
+    FontFamily, FontSize, Message, MessageStyle, NodeStackFlattener, Styleable,
+};
 
 /// Defines how to convert a [`MessageStyle`] into [`TextFormat`] for egui.
 ///
@@ -56,6 +78,38 @@ use crate::{Message, MessageStyle, StackFlattener};
 ///         .underline()),
 /// );
 /// ```
+///
+/// # Bold text
+///
+/// [`TextFormat`] has no way to mark text as bold, so by default bold text without a color of
+/// its own is made distinct by showing it in [`StyleToFormat::bold_color`], which is white like
+/// egui's own [`RichText::strong`] in the dark theme. For a light theme, set this to a darker
+/// color than [`StyleToFormat::default_color`]. If you have a bold variant of your font, you can
+/// also register it as its own font family in egui, and use it for bold text through
+/// [`StyleToFormat::bold_font_id`]:
+///
+/// ```
+/// use expedition::{egui::StyleToFormat, Color32, MessageStyle, Styleable};
+/// use egui::{FontFamily, FontId, TextFormat};
+///
+/// let style_to_format = StyleToFormat {
+///     // this family must be registered in the egui `FontDefinitions`
+///     bold_font_id: Some(FontId::new(14.0, FontFamily::Name("bold".into()))),
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(
+///     TextFormat {
+///         font_id: FontId::new(14.0, FontFamily::Name("bold".into())),
+///         color: Color32::WHITE,
+///         ..Default::default()
+///     },
+///     style_to_format.to_format(MessageStyle::default()
+///         .bold()),
+/// );
+/// ```
+///
+/// [`RichText::strong`]: egui::RichText::strong
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StyleToFormat {
@@ -65,6 +119,11 @@ pub struct StyleToFormat {
     pub background: Color32,
     /// [`TextFormat::color`]
     pub default_color: Color32,
+    /// [`TextFormat::font_id`] of bold text, or [`None`] to use [`StyleToFormat::font_id`]
+    pub bold_font_id: Option<FontId>,
+    /// [`TextFormat::color`] of bold text which has no color set, or [`None`] to use
+    /// [`StyleToFormat::default_color`]
+    pub bold_color: Option<Color32>,
     /// [`Stroke::width`] of [`TextFormat::underline`]
     pub underline_width: f32,
     /// [`Stroke::width`] of [`TextFormat::strikethrough`]
@@ -79,6 +138,8 @@ impl Default for StyleToFormat {
             font_id: FontId::default(),
             background: Color32::TRANSPARENT,
             default_color: Color32::GRAY,
+            bold_font_id: None,
+            bold_color: Some(Color32::WHITE),
             underline_width: 1.0,
             strikethrough_width: 1.0,
             valign: Align::BOTTOM,
@@ -88,10 +149,31 @@ impl Default for StyleToFormat {
 
 impl StyleToFormat {
     /// Converts a [`MessageStyle`] to a [`TextFormat`] using the defaults provided in this struct.
+    ///
+    /// [`MessageStyle::size`] is resolved relative to the size of the font that would otherwise
+    /// be used, and [`MessageStyle::family`] replaces the family of that font.
     pub fn to_format(&self, style: MessageStyle) -> TextFormat {
-        let foreground = style.color.unwrap_or(self.default_color);
+        let bold = style.bold == Some(true);
+        let default_color = match self.bold_color {
+            Some(bold_color) if bold => bold_color,
+            _ => self.default_color,
+        };
+        let foreground = style.color.unwrap_or(default_color);
+        let mut font_id = match &self.bold_font_id {
+            Some(bold_font_id) if bold => bold_font_id.clone(),
+            _ => self.font_id.clone(),
+        };
+        if let Some(size) = style.size {
+            font_id.size = size.resolve(font_id.size);
+        }
+        match style.family {
+            Some(FontFamily::Proportional) => font_id.family = egui::FontFamily::Proportional,
+            Some(FontFamily::Monospace) => font_id.family = egui::FontFamily::Monospace,
+            None => {}
+        }
+
         TextFormat {
-            font_id: self.font_id.clone(),
+            font_id,
             color: foreground,
             background: self.background,
             italics: style.italic == Some(true),
@@ -109,13 +191,1651 @@ impl StyleToFormat {
 
     /// Converts a hierarchy of [`Message`] nodes to a sequence of [`LayoutJob`] styled sections.
     ///
-    /// This uses [`Message::flatten`] to perform the conversion from hierarchy to [`LayoutJob::append`] calls.
+    /// This uses [`Message::render`] with a [`JobRenderer`] to perform the conversion from
+    /// hierarchy to [`LayoutJob::append`] calls.
     pub fn to_job(&self, text: &Message) -> LayoutJob {
+        let mut renderer = JobRenderer::new(self);
+        text.render(&mut renderer)
+            .unwrap_or_else(|never| match never {});
+        renderer.finish()
+    }
+}
+
+/// Renders [`Message`]s to a [`LayoutJob`], using a [`StyleToFormat`].
+///
+/// Rendering multiple messages appends them all to the same job.
+#[derive(Debug, Clone)]
+pub struct JobRenderer<'a> {
+    format: &'a StyleToFormat,
+    job: LayoutJob,
+}
+
+impl<'a> JobRenderer<'a> {
+    /// Creates a renderer with an empty job.
+    pub fn new(format: &'a StyleToFormat) -> Self {
+        Self {
+            format,
+            job: LayoutJob::default(),
+        }
+    }
+
+    /// Gets the job that has been rendered to.
+    pub fn finish(self) -> LayoutJob {
+        self.job
+    }
+}
+
+impl Renderer for JobRenderer<'_> {
+    type Error = Infallible;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Infallible> {
+        self.job.append(content, 0.0, self.format.to_format(style));
+        Ok(())
+    }
+}
+
+/// Fields of a [`TextFormat`] which could not be represented in a [`MessageStyle`] when converting
+/// using [`StyleToFormat::from_format`] or [`StyleToFormat::from_job`].
+///
+/// Each field is `true` if at least one [`TextFormat`] had a value for that field which is
+/// different to what [`StyleToFormat`] would have created, and was therefore lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LossyFields {
+    /// [`TextFormat::font_id`] had a named family which was not the family of
+    /// [`StyleToFormat::font_id`] or [`StyleToFormat::bold_font_id`].
+    pub font: bool,
+    /// [`TextFormat::background`] was not [`StyleToFormat::background`].
+    pub background: bool,
+    /// [`TextFormat::valign`] was not [`StyleToFormat::valign`].
+    pub valign: bool,
+    /// [`TextFormat::underline`] or [`TextFormat::strikethrough`] had a different width to the
+    /// one in [`StyleToFormat`], or a different color to the text.
+    pub stroke: bool,
+    /// A section of a [`LayoutJob`] had a non-zero leading space.
+    pub leading_space: bool,
+    /// The font could not tell if the text was bold, so it may have been read back incorrectly.
+    ///
+    /// This happens if [`StyleToFormat::bold_font_id`] has the same family as
+    /// [`StyleToFormat::font_id`], or if the family of the text was overridden, replacing the
+    /// family of the bold font. It also happens if [`TextFormat::color`] was
+    /// [`StyleToFormat::bold_color`] with no bold font to tell if the text was bold, in which case
+    /// it is read back as bold with no color, but it may have been text which was not bold with
+    /// that color.
+    pub bold: bool,
+}
+
+impl LossyFields {
+    /// Gets if no fields were lost.
+    #[must_use]
+    pub fn is_lossless(&self) -> bool {
+        self == &Self::default()
+    }
+
+    fn merge_from(&mut self, from: Self) {
+        self.font |= from.font;
+        self.background |= from.background;
+        self.valign |= from.valign;
+        self.stroke |= from.stroke;
+        self.leading_space |= from.leading_space;
+        self.bold |= from.bold;
+    }
+}
+
+impl StyleToFormat {
+    /// Converts a [`TextFormat`] back into a [`MessageStyle`], as the inverse of
+    /// [`StyleToFormat::to_format`].
+    ///
+    /// Values which are equal to the defaults in this struct are treated as unset, so for a
+    /// style `s` with no fields set to `false`, `from_format(&to_format(s))` gives back `s`.
+    /// Any fields of the format which can't be represented in a [`MessageStyle`] are reported in
+    /// the returned [`LossyFields`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use expedition::{egui::StyleToFormat, Color32, MessageStyle, Styleable};
+    ///
+    /// let style_to_format = StyleToFormat::default();
+    /// let style = MessageStyle::default().color(Color32::RED).italic().underline();
+    ///
+    /// let (converted, lossy) = style_to_format.from_format(&style_to_format.to_format(style));
+    /// assert_eq!(style, converted);
+    /// assert!(lossy.is_lossless());
+    /// ```
+    pub fn from_format(&self, format: &TextFormat) -> (MessageStyle, LossyFields) {
+        let mut lossy = LossyFields::default();
+
+        // the size may be overridden per message, so the bold font can only be told apart by its
+        // family, which is replaced if the message overrides the family
+        let bold_family = self
+            .bold_font_id
+            .as_ref()
+            .map(|bold_font_id| &bold_font_id.family)
+            .filter(|&family| family != &self.font_id.family);
+        let overridden = format.font_id.family != self.font_id.family
+            && Some(&format.font_id.family) != bold_family;
+        let bold_font = !overridden && bold_family == Some(&format.font_id.family);
+        // otherwise, bold text can only be found from its color
+        let bold_color = (bold_family.is_none() || overridden)
+            && self.bold_color == Some(format.color)
+            && self.bold_color != Some(self.default_color);
+        let bold = bold_font || bold_color;
+        // bold text is only certain when read from the bold font
+        lossy.bold =
+            bold_color || (self.bold_font_id.is_some() && (bold_family.is_none() || overridden));
+
+        let base_font = match &self.bold_font_id {
+            Some(bold_font_id) if bold => bold_font_id,
+            _ => &self.font_id,
+        };
+        let size = (format.font_id.size != base_font.size)
+            .then_some(FontSize::Points(format.font_id.size));
+        let family = if format.font_id.family == base_font.family {
+            None
+        } else {
+            match format.font_id.family {
+                egui::FontFamily::Proportional => Some(FontFamily::Proportional),
+                egui::FontFamily::Monospace => Some(FontFamily::Monospace),
+                egui::FontFamily::Name(_) => {
+                    lossy.font = true;
+                    None
+                }
+            }
+        };
+        let default_color = match self.bold_color {
+            Some(bold_color) if bold => bold_color,
+            _ => self.default_color,
+        };
+        let color = (format.color != default_color).then_some(format.color);
+
+        let mut stroke = |stroke: Stroke, width: f32| {
+            if stroke.width <= 0.0 || stroke.color == Color32::TRANSPARENT {
+                return None;
+            }
+            if stroke.width != width || stroke.color != format.color {
+                lossy.stroke = true;
+            }
+            Some(true)
+        };
+        let underline = stroke(format.underline, self.underline_width);
+        let strikethrough = stroke(format.strikethrough, self.strikethrough_width);
+
+        lossy.background = format.background != self.background;
+        lossy.valign = format.valign != self.valign;
+
+        let style = MessageStyle {
+            color,
+            bold: bold.then_some(true),
+            italic: format.italics.then_some(true),
+            underline,
+            strikethrough,
+            size,
+            family,
+        };
+        (style, lossy)
+    }
+
+    /// Converts a [`LayoutJob`] back into a [`Message`], as the inverse of
+    /// [`StyleToFormat::to_job`].
+    ///
+    /// Each section of the job is converted using [`StyleToFormat::from_format`], and the
+    /// resulting message is in the form given by [`Message::normalized`]. Settings of the job
+    /// itself, such as wrapping, are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use expedition::{egui::StyleToFormat, Color32, IntoMessage, Styleable};
+    /// use egui::{text::LayoutJob, Align, TextFormat};
+    ///
+    /// let style_to_format = StyleToFormat::default();
+    /// let msg = "Hello "
+    ///     .with("world".color(Color32::RED).italic());
+    ///
+    /// let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+    /// assert_eq!(msg.normalized(), converted);
+    /// assert!(lossy.is_lossless());
+    ///
+    /// let mut job = LayoutJob::default();
+    /// job.append("raised", 0.0, TextFormat {
+    ///     valign: Align::TOP,
+    ///     ..style_to_format.to_format(Default::default())
+    /// });
+    ///
+    /// let (converted, lossy) = style_to_format.from_job(&job);
+    /// assert_eq!("raised".into_text(), converted);
+    /// assert!(lossy.valign);
+    /// ```
+    pub fn from_job(&self, job: &LayoutJob) -> (Message, LossyFields) {
+        let mut lossy = LossyFields::default();
+        let segments: Vec<_> = job
+            .sections
+            .iter()
+            .map(|section| {
+                let (style, section_lossy) = self.from_format(&section.format);
+                lossy.merge_from(section_lossy);
+                lossy.leading_space |= section.leading_space != 0.0;
+                (&job.text[section.byte_range.clone()], style)
+            })
+            .collect();
+        (Message::from_segments(segments), lossy)
+    }
+}
+
+/// Statistics about how effective a [`GalleyCache`] has been.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CacheStats {
+    /// Number of lookups which reused a cached galley.
+    pub hits: u64,
+    /// Number of lookups which had to lay out a new galley.
+    pub misses: u64,
+    /// Number of galleys removed from the cache to stay within its limits.
+    pub evictions: u64,
+    /// Number of galleys currently in the cache.
+    pub len: usize,
+}
+
+impl CacheStats {
+    /// Fraction of lookups which were hits, or `0.0` if there have been no lookups.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// Caches the [`Galley`]s laid out from the [`LayoutJob`]s created by [`StyleToFormat::to_job`],
+/// for messages which are redrawn every frame.
+///
+/// A [`Galley`] can be shown directly in a [`Label`], so a cached message is neither converted
+/// into a new job nor laid out again. Galleys are keyed by the message, the [`StyleToFormat`]
+/// settings, the wrap width and the scale of the fonts, so changing any of these lays out a new
+/// galley. Call [`GalleyCache::clear`] if the font definitions change.
+///
+/// Call [`GalleyCache::end_frame`] once per frame to evict galleys which have not been used for
+/// [`GalleyCache::max_age`] frames. If the cache grows beyond [`GalleyCache::capacity`] galleys,
+/// the least recently used galley is evicted.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{egui::{GalleyCache, StyleToFormat}, Color32, IntoMessage, Styleable};
+/// # use egui::{text::Fonts, FontDefinitions};
+/// # let fonts = Fonts::new(1.0, 1024, FontDefinitions::default());
+///
+/// let style_to_format = StyleToFormat::default();
+/// let mut cache = GalleyCache::default();
+/// let msg = "HP: ".with("100".color(Color32::GREEN));
+///
+/// for _ in 0..3 {
+///     // in a `Ui`, get the fonts using `ui.fonts(|fonts| ...)`
+///     let galley = cache.get(&fonts, &style_to_format, &msg, f32::INFINITY);
+///     // ui.label(galley);
+///     cache.end_frame();
+/// }
+///
+/// let stats = cache.stats();
+/// assert_eq!((2, 1), (stats.hits, stats.misses));
+/// ```
+#[derive(Debug, Clone)]
+pub struct GalleyCache {
+    /// Number of frames that a galley can go unused for before it is evicted.
+    pub max_age: u64,
+    /// Maximum number of galleys in the cache.
+    pub capacity: usize,
+    /// Entries by the hash of their key, which may be shared by multiple entries.
+    entries: HashMap<u64, Vec<CacheEntry>>,
+    /// Hash and last used frame of each entry, by when it was last used, from least to most
+    /// recently used.
+    order: BTreeMap<u64, (u64, u64)>,
+    /// Incremented on every lookup, so that each entry was last used at a unique tick.
+    tick: u64,
+    frame: u64,
+    stats: CacheStats,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    /// Kept to tell apart messages with the same hash.
+    message: Message,
+    settings: CacheSettings,
+    galley: Arc<Galley>,
+    tick: u64,
+}
+
+/// Hash of the [`StyleToFormat`] settings, and bit patterns of the wrap width and the scale of the
+/// fonts, that a galley was laid out with.
+type CacheSettings = (u64, u32, u32);
+
+impl Default for GalleyCache {
+    fn default() -> Self {
+        Self {
+            max_age: 60,
+            capacity: 1024,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            frame: 0,
+            stats: CacheStats::default(),
+        }
+    }
+}
+
+impl GalleyCache {
+    /// Gets the galley for `message` laid out using `format` and `fonts`, wrapped at
+    /// `wrap_width`, laying it out if it is not cached.
+    ///
+    /// Pass [`f32::INFINITY`] as the `wrap_width` to not wrap the text.
+    pub fn get(
+        &mut self,
+        fonts: &Fonts,
+        format: &StyleToFormat,
+        message: &Message,
+        wrap_width: f32,
+    ) -> Arc<Galley> {
+        let settings = (
+            format.settings_hash(),
+            wrap_width.to_bits(),
+            fonts.pixels_per_point().to_bits(),
+        );
+        let hash = {
+            let mut hasher = DefaultHasher::new();
+            settings.hash(&mut hasher);
+            message.hash(&mut hasher);
+            hasher.finish()
+        };
+        let tick = self.tick;
+        self.tick += 1;
+
+        let cached = self.entries.get_mut(&hash).and_then(|bucket| {
+            bucket
+                .iter_mut()
+                .find(|entry| entry.settings == settings && entry.message == *message)
+        });
+        if let Some(entry) = cached {
+            self.order.remove(&entry.tick);
+            self.order.insert(tick, (hash, self.frame));
+            entry.tick = tick;
+            self.stats.hits += 1;
+            return Arc::clone(&entry.galley);
+        }
+
+        self.stats.misses += 1;
+        let mut job = format.to_job(message);
+        job.wrap.max_width = wrap_width;
+        let galley = fonts.layout_job(job);
+        self.entries.entry(hash).or_default().push(CacheEntry {
+            message: message.clone(),
+            settings,
+            galley: Arc::clone(&galley),
+            tick,
+        });
+        self.order.insert(tick, (hash, self.frame));
+        while self.order.len() > self.capacity {
+            self.evict_oldest();
+        }
+        self.stats.len = self.order.len();
+        galley
+    }
+
+    /// Marks the end of a frame, evicting all galleys which have not been used for
+    /// [`GalleyCache::max_age`] frames.
+    pub fn end_frame(&mut self) {
+        while let Some((_, &(_, last_used))) = self.order.first_key_value() {
+            if self.frame - last_used < self.max_age {
+                break;
+            }
+            self.evict_oldest();
+        }
+        self.stats.len = self.order.len();
+        self.frame += 1;
+    }
+
+    /// Gets the statistics of this cache since it was created, or since the last
+    /// [`GalleyCache::reset_stats`].
+    #[must_use]
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Resets the hit, miss and eviction counts to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats {
+            len: self.order.len(),
+            ..Default::default()
+        };
+    }
+
+    /// Removes all galleys from the cache.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.stats.len = 0;
+    }
+
+    fn evict_oldest(&mut self) {
+        let Some((tick, (hash, _))) = self.order.pop_first() else {
+            return;
+        };
+        if let hash_map::Entry::Occupied(mut bucket) = self.entries.entry(hash) {
+            bucket.get_mut().retain(|entry| entry.tick != tick);
+            if bucket.get().is_empty() {
+                bucket.remove();
+            }
+        }
+        self.stats.evictions += 1;
+    }
+}
+
+impl StyleToFormat {
+    /// Hashes all settings, using the bit patterns of floating point values.
+    fn settings_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.font_id.hash(&mut hasher);
+        self.background.hash(&mut hasher);
+        self.default_color.hash(&mut hasher);
+        self.bold_font_id.hash(&mut hasher);
+        self.bold_color.hash(&mut hasher);
+        self.underline_width.to_bits().hash(&mut hasher);
+        self.strikethrough_width.to_bits().hash(&mut hasher);
+        self.valign.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// The message node that a section of a [`LayoutJob`] was created from.
+#[derive(Debug, Clone)]
+struct SectionSource {
+    path: Vec<usize>,
+    chars: Range<usize>,
+}
+
+impl StyleToFormat {
+    /// Like [`StyleToFormat::to_job`], but also returns the source node of each section in the
+    /// job. Nodes with no content do not create a section.
+    fn to_job_with_sources(&self, text: &Message) -> (LayoutJob, Vec<SectionSource>) {
         let mut job = LayoutJob::default();
-        let mut flattener = StackFlattener::new(|content, style| {
+        let mut sources = Vec::new();
+        let mut chars = 0;
+        text.flatten(&mut NodeStackFlattener::new(|content, style, cx| {
+            if content.is_empty() {
+                return;
+            }
+            let len = content.chars().count();
             job.append(content, 0.0, self.to_format(style));
+            sources.push(SectionSource {
+                path: cx.path.to_vec(),
+                chars: chars..chars + len,
+            });
+            chars += len;
+        }));
+        (job, sources)
+    }
+}
+
+/// A widget which displays a [`Message`], and reports which part of the message the pointer is
+/// interacting with.
+///
+/// The message is laid out using a [`StyleToFormat`], which can be set using
+/// [`MessageLabel::format`]. When shown using [`MessageLabel::show`], the returned
+/// [`MessageLabelResponse`] holds the [`MessageHit`]s for the hovered and clicked characters,
+/// which can be used to find the original node of the message under the pointer.
+///
+/// If [`MessageLabel::selectable`] is enabled, the text can be selected by dragging over it, and
+/// the selected text copied as plain text.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{egui::MessageLabel, Color32, IntoMessage, Styleable};
+///
+/// # egui::__run_test_ui(|ui| {
+/// let msg = "Click on a "
+///     .with("link".color(Color32::LIGHT_BLUE).underline());
+///
+/// let res = MessageLabel::new(&msg).selectable(true).show(ui);
+/// if let Some(hit) = res.clicked {
+///     if hit.path == [0] {
+///         // the link was clicked
+///     }
+/// }
+/// # });
+/// ```
+#[must_use = "You should put this widget in an ui with `ui.add(widget);`"]
+#[derive(Debug, Clone)]
+pub struct MessageLabel<'a> {
+    message: &'a Message,
+    format: StyleToFormat,
+    wrap: bool,
+    selectable: bool,
+}
+
+/// A character of a [`Message`] displayed in a [`MessageLabel`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageHit {
+    /// Index path of the node which the character belongs to - see [`Message::node_at`].
+    pub path: Vec<usize>,
+    /// Index of the character in the plain text of the message.
+    pub char_index: usize,
+    /// Range of character indices in the plain text of the message which the content of the
+    /// node covers.
+    pub node_chars: Range<usize>,
+}
+
+/// The result of showing a [`MessageLabel`].
+#[derive(Debug)]
+pub struct MessageLabelResponse {
+    /// The response of the whole label.
+    pub response: Response,
+    /// The character under the pointer, if it is hovering over the text.
+    pub hovered: Option<MessageHit>,
+    /// The character which was clicked this frame, if any.
+    pub clicked: Option<MessageHit>,
+    /// Range of character indices of the selected plain text, if any text is selected.
+    pub selection: Option<Range<usize>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct LabelState {
+    /// Start and end character indices of the selection, which may be in any order.
+    selection: Option<(usize, usize)>,
+}
+
+impl<'a> MessageLabel<'a> {
+    /// Creates a label displaying the given message.
+    pub fn new(message: &'a Message) -> Self {
+        Self {
+            message,
+            format: StyleToFormat::default(),
+            wrap: true,
+            selectable: false,
+        }
+    }
+
+    /// Sets how the message is converted into a [`LayoutJob`].
+    pub fn format(mut self, format: StyleToFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets if the text wraps at the available width. Enabled by default.
+    pub const fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Sets if the text can be selected and copied. Disabled by default.
+    pub const fn selectable(mut self, selectable: bool) -> Self {
+        self.selectable = selectable;
+        self
+    }
+
+    /// Lays out and displays the message.
+    pub fn show(self, ui: &mut Ui) -> MessageLabelResponse {
+        let (mut job, sources) = self.format.to_job_with_sources(self.message);
+        job.wrap.max_width = if self.wrap {
+            ui.available_width()
+        } else {
+            f32::INFINITY
+        };
+        let galley = ui.fonts(|fonts| fonts.layout_job(job));
+
+        let sense = if self.selectable {
+            Sense::click_and_drag()
+        } else {
+            Sense::click()
+        };
+        let (rect, mut response) = ui.allocate_exact_size(galley.size(), sense);
+
+        let hit_at = |pos: Pos2| {
+            glyph_at(&galley, pos - rect.min).map(|(char_index, section)| {
+                let source = &sources[section];
+                MessageHit {
+                    path: source.path.clone(),
+                    char_index,
+                    node_chars: source.chars.clone(),
+                }
+            })
+        };
+        let hovered = response.hover_pos().and_then(hit_at);
+        let clicked = if response.clicked() {
+            response.interact_pointer_pos().and_then(hit_at)
+        } else {
+            None
+        };
+
+        let mut selection = None;
+        if self.selectable {
+            let mut state = ui
+                .data(|data| data.get_temp::<LabelState>(response.id))
+                .unwrap_or_default();
+
+            if let Some(pos) = response.interact_pointer_pos() {
+                let index = galley.cursor_from_pos(pos - rect.min).ccursor.index;
+                if response.drag_started() {
+                    state.selection = Some((index, index));
+                } else if response.dragged() {
+                    if let Some((anchor, _)) = state.selection {
+                        state.selection = Some((anchor, index));
+                    }
+                }
+            }
+            let pressed_elsewhere = ui.input(|i| i.pointer.any_pressed()) && !response.hovered();
+            if response.clicked() || pressed_elsewhere {
+                state.selection = None;
+            }
+
+            selection = state
+                .selection
+                .map(|(a, b)| a.min(b)..a.max(b))
+                .filter(|range| !range.is_empty());
+            if let Some(range) = &selection {
+                if ui.input(|i| i.events.iter().any(|event| matches!(event, Event::Copy))) {
+                    let text: String = galley
+                        .text()
+                        .chars()
+                        .skip(range.start)
+                        .take(range.len())
+                        .collect();
+                    ui.output_mut(|output| output.copied_text = text);
+                }
+            }
+
+            ui.data_mut(|data| data.insert_temp(response.id, state));
+            response = response.on_hover_cursor(CursorIcon::Text);
+        }
+
+        if ui.is_rect_visible(rect) {
+            if let Some(range) = &selection {
+                let color = ui.visuals().selection.bg_fill;
+                paint_selection(ui.painter(), &galley, rect.min, range, color);
+            }
+            ui.painter().galley(rect.min, galley);
+        }
+
+        MessageLabelResponse {
+            response,
+            hovered,
+            clicked,
+            selection,
+        }
+    }
+}
+
+impl Widget for MessageLabel<'_> {
+    fn ui(self, ui: &mut Ui) -> Response {
+        self.show(ui).response
+    }
+}
+
+/// Finds the character index and section index of the glyph at `pos`, relative to the galley.
+fn glyph_at(galley: &Galley, pos: Vec2) -> Option<(usize, usize)> {
+    let mut row_start = 0;
+    for row in &galley.rows {
+        if pos.y >= row.min_y() && pos.y < row.max_y() {
+            return row
+                .glyphs
+                .iter()
+                .position(|glyph| pos.x >= glyph.pos.x && pos.x < glyph.max_x())
+                .map(|column| {
+                    let section = row.glyphs[column].section_index as usize;
+                    (row_start + column, section)
+                });
+        }
+        row_start += row.char_count_including_newline();
+    }
+    None
+}
+
+fn paint_selection(
+    painter: &Painter,
+    galley: &Galley,
+    origin: Pos2,
+    range: &Range<usize>,
+    color: Color32,
+) {
+    let mut row_start = 0;
+    for row in &galley.rows {
+        let row_end = row_start + row.char_count_excluding_newline();
+        let start = range.start.max(row_start);
+        let end = range.end.min(row_end);
+        if start < end {
+            let rect = Rect::from_min_max(
+                pos2(row.x_offset(start - row_start), row.min_y()),
+                pos2(row.x_offset(end - row_start), row.max_y()),
+            );
+            painter.rect_filled(rect.translate(origin.to_vec2()), 0.0, color);
+        }
+        row_start += row.char_count_including_newline();
+    }
+}
+
+/// A WYSIWYG editor for a [`Message`].
+///
+/// The editor holds the text being edited along with the style of every character, and shows a
+/// toolbar for changing the style of the selected text, followed by a multiline [`TextEdit`].
+///
+/// Decorations are toggled using [`Styleable`] semantics: if all of the selected text already
+/// has a decoration enabled, it is disabled using e.g. [`Styleable::no_bold`], and otherwise it
+/// is enabled using e.g. [`Styleable::bold`]. If no text is selected, the style applies to the
+/// next text typed at the cursor. Newly typed text otherwise takes the style of the character
+/// before it.
+///
+/// After any change, [`MessageEditorResponse::changed`] holds the edited message, in the form
+/// given by [`Message::normalized`].
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{egui::MessageEditor, IntoMessage, Styleable};
+///
+/// let mut editor = MessageEditor::new(&"Hello ".with("world".bold()));
+///
+/// # egui::__run_test_ui(|ui| {
+/// if let Some(msg) = editor.show(ui).changed {
+///     // store the edited message
+/// }
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageEditor {
+    text: String,
+    /// Final style of each character of `text`.
+    styles: Vec<MessageStyle>,
+    /// Character range of the current selection.
+    selection: Range<usize>,
+    /// Style applied to the next text inserted at the cursor, if it has been set while nothing
+    /// was selected.
+    pending: Option<MessageStyle>,
+    format: StyleToFormat,
+}
+
+/// The result of showing a [`MessageEditor`].
+#[derive(Debug)]
+pub struct MessageEditorResponse {
+    /// The response of the text edit.
+    pub response: Response,
+    /// The edited message, if it was changed this frame.
+    pub changed: Option<Message>,
+}
+
+impl MessageEditor {
+    /// Creates an editor for the given message.
+    pub fn new(message: &Message) -> Self {
+        let mut editor = Self {
+            text: String::new(),
+            styles: Vec::new(),
+            selection: 0..0,
+            pending: None,
+            format: StyleToFormat::default(),
+        };
+        editor.set_message(message);
+        editor
+    }
+
+    /// Sets how the message is converted into a [`LayoutJob`] for display.
+    #[must_use]
+    pub fn format(mut self, format: StyleToFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Replaces the message being edited.
+    pub fn set_message(&mut self, message: &Message) {
+        self.text.clear();
+        self.styles.clear();
+        for (content, style) in message.segments() {
+            self.text.push_str(content);
+            self.styles
+                .extend(std::iter::repeat(style).take(content.chars().count()));
+        }
+        self.selection = 0..0;
+        self.pending = None;
+    }
+
+    /// Gets the message being edited, in the form given by [`Message::normalized`].
+    pub fn message(&self) -> Message {
+        Message::from_segments(runs(&self.text, &self.styles))
+    }
+
+    /// Gets the character range of the current selection.
+    pub fn selection(&self) -> Range<usize> {
+        let len = self.styles.len();
+        self.selection.start.min(len)..self.selection.end.min(len)
+    }
+
+    /// Applies `f` to the style of the selected text, or to the style of the next text typed if
+    /// nothing is selected.
+    pub fn apply_style(&mut self, f: impl Fn(MessageStyle) -> MessageStyle) {
+        let selection = self.selection();
+        if selection.is_empty() {
+            self.pending = Some(f(self.cursor_style()));
+        } else {
+            for style in &mut self.styles[selection] {
+                *style = f(*style);
+            }
+        }
+    }
+
+    /// Toggles bold on the selected text.
+    pub fn toggle_bold(&mut self) {
+        self.toggle(|s| s.bold, Styleable::bold, Styleable::no_bold);
+    }
+
+    /// Toggles italic on the selected text.
+    pub fn toggle_italic(&mut self) {
+        self.toggle(|s| s.italic, Styleable::italic, Styleable::no_italic);
+    }
+
+    /// Toggles underline on the selected text.
+    pub fn toggle_underline(&mut self) {
+        self.toggle(
+            |s| s.underline,
+            Styleable::underline,
+            Styleable::no_underline,
+        );
+    }
+
+    /// Toggles strikethrough on the selected text.
+    pub fn toggle_strikethrough(&mut self) {
+        self.toggle(
+            |s| s.strikethrough,
+            Styleable::strikethrough,
+            Styleable::no_strikethrough,
+        );
+    }
+
+    /// Sets the color of the selected text.
+    pub fn set_color(&mut self, color: Option<Color32>) {
+        self.apply_style(|style| style.with_color(color));
+    }
+
+    /// Shows the toolbar and text edit.
+    pub fn show(&mut self, ui: &mut Ui) -> MessageEditorResponse {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            type Toggle = (
+                RichText,
+                fn(&MessageStyle) -> Option<bool>,
+                fn(&mut MessageEditor),
+            );
+            let toggles: [Toggle; 4] = [
+                (RichText::new("B").strong(), |s| s.bold, Self::toggle_bold),
+                (
+                    RichText::new("I").italics(),
+                    |s| s.italic,
+                    Self::toggle_italic,
+                ),
+                (
+                    RichText::new("U").underline(),
+                    |s| s.underline,
+                    Self::toggle_underline,
+                ),
+                (
+                    RichText::new("S").strikethrough(),
+                    |s| s.strikethrough,
+                    Self::toggle_strikethrough,
+                ),
+            ];
+            for (label, get, toggle) in toggles {
+                if ui.selectable_label(self.all_enabled(get), label).clicked() {
+                    toggle(self);
+                    changed = true;
+                }
+            }
+
+            let selected_color = self.selected_styles().next().and_then(|s| s.color);
+            let mut color = selected_color.unwrap_or(self.format.default_color);
+            if color_picker::color_edit_button_srgba(ui, &mut color, Alpha::Opaque).changed() {
+                self.set_color(Some(color));
+                changed = true;
+            }
+            if ui.button("No color").clicked() {
+                self.set_color(None);
+                changed = true;
+            }
+        });
+
+        let mut text = self.text.clone();
+        let output = {
+            let (old_text, old_styles, pending) = (&self.text, &self.styles, self.pending);
+            let format = &self.format;
+            let mut layouter = |ui: &Ui, new_text: &str, wrap_width: f32| {
+                let styles = adjust_styles(old_text, old_styles, new_text, pending);
+                let mut job = LayoutJob::default();
+                for (content, style) in runs(new_text, &styles) {
+                    job.append(content, 0.0, format.to_format(style));
+                }
+                job.wrap.max_width = wrap_width;
+                ui.fonts(|fonts| fonts.layout_job(job))
+            };
+            TextEdit::multiline(&mut text)
+                .layouter(&mut layouter)
+                .show(ui)
+        };
+
+        if text != self.text {
+            self.styles = adjust_styles(&self.text, &self.styles, &text, self.pending);
+            self.text = text;
+            changed = true;
+        }
+        if let Some(cursor_range) = output.cursor_range {
+            let selection = cursor_range.as_sorted_char_range();
+            if selection != self.selection {
+                self.selection = selection;
+                self.pending = None;
+            }
+        }
+
+        MessageEditorResponse {
+            response: output.response,
+            changed: changed.then(|| self.message()),
+        }
+    }
+
+    /// Style of the text that would be inserted at the cursor.
+    fn cursor_style(&self) -> MessageStyle {
+        self.pending.unwrap_or_else(|| {
+            let cursor = self.selection().start;
+            insert_style(&self.styles, cursor)
+        })
+    }
+
+    /// Styles of the selected characters, or the style at the cursor if nothing is selected.
+    fn selected_styles(&self) -> impl Iterator<Item = MessageStyle> + '_ {
+        let selection = self.selection();
+        let cursor = selection
+            .is_empty()
+            .then(|| self.cursor_style())
+            .into_iter();
+        self.styles[selection].iter().copied().chain(cursor)
+    }
+
+    fn all_enabled(&self, get: fn(&MessageStyle) -> Option<bool>) -> bool {
+        self.selected_styles()
+            .all(|style| get(&style) == Some(true))
+    }
+
+    fn toggle(
+        &mut self,
+        get: fn(&MessageStyle) -> Option<bool>,
+        on: fn(MessageStyle) -> MessageStyle,
+        off: fn(MessageStyle) -> MessageStyle,
+    ) {
+        if self.all_enabled(get) {
+            self.apply_style(off);
+        } else {
+            self.apply_style(on);
+        }
+    }
+}
+
+/// Shows the entries of a [`MessageLog`], with controls for filtering and copying them.
+///
+/// Only the rows which are scrolled into view are laid out, so logs with tens of thousands of
+/// entries can be shown every frame. Every entry is assumed to take up a single row, so
+/// messages should not contain line breaks. The view sticks to the bottom of the log as new
+/// entries are added, unless it has been scrolled up.
+///
+/// The indices of the entries which pass the filter are cached, and only entries added since
+/// the last frame are checked against the filter, unless the filter has changed.
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{console::{MessageLog, Severity}, egui::ConsoleView};
+///
+/// let mut log = MessageLog::new(10_000);
+/// let mut console = ConsoleView::default();
+/// log.push(Severity::Info, "Server started");
+///
+/// # egui::__run_test_ui(|ui| {
+/// console.show(ui, &log);
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConsoleView {
+    /// How messages are converted into [`LayoutJob`]s.
+    pub format: StyleToFormat,
+    /// Whether to show the time that each entry was added.
+    pub show_time: bool,
+    filter: String,
+    min_severity: Severity,
+    /// [`MessageLog::total_pushed`] index of each entry which passes the filter.
+    matches: VecDeque<u64>,
+    /// Entries before this [`MessageLog::total_pushed`] index have been checked.
+    scanned: u64,
+    /// Filter and severity that `matches` was built with.
+    scanned_with: Option<(String, Severity)>,
+    galleys: GalleyCache,
+}
+
+impl Default for ConsoleView {
+    fn default() -> Self {
+        Self {
+            format: StyleToFormat::default(),
+            show_time: true,
+            filter: String::new(),
+            min_severity: Severity::Trace,
+            matches: VecDeque::new(),
+            scanned: 0,
+            scanned_with: None,
+            galleys: GalleyCache::default(),
+        }
+    }
+}
+
+impl ConsoleView {
+    /// Sets how messages are converted into [`LayoutJob`]s.
+    #[must_use]
+    pub fn format(mut self, format: StyleToFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Gets the text that entries must contain to be shown.
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Sets the text that entries must contain to be shown, ignoring case.
+    pub fn set_filter(&mut self, filter: impl Into<String>) {
+        self.filter = filter.into();
+    }
+
+    /// Gets the least important severity of the entries which are shown.
+    pub const fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    /// Sets the least important severity of the entries which are shown.
+    pub fn set_min_severity(&mut self, severity: Severity) {
+        self.min_severity = severity;
+    }
+
+    /// Gets the entries of `log` which pass the filter, from oldest to newest.
+    pub fn matching<'a>(&mut self, log: &'a MessageLog) -> Vec<&'a LogEntry> {
+        self.update_matches(log);
+        let first = log.total_pushed() - log.len() as u64;
+        self.matches
+            .iter()
+            .filter_map(|id| log.get((id - first) as usize))
+            .collect()
+    }
+
+    /// Shows the toolbar and the entries of `log`.
+    pub fn show(&mut self, ui: &mut Ui, log: &MessageLog) {
+        ui.horizontal(|ui| {
+            ui.add(
+                TextEdit::singleline(&mut self.filter)
+                    .hint_text("Filter")
+                    .desired_width(200.0),
+            );
+            ComboBox::from_id_source(ui.id().with("min_severity"))
+                .selected_text(self.min_severity.as_str())
+                .show_ui(ui, |ui| {
+                    for severity in Severity::ALL {
+                        ui.selectable_value(&mut self.min_severity, severity, severity.as_str());
+                    }
+                });
+            ui.checkbox(&mut self.show_time, "Time");
+            if ui.button("Copy").clicked() {
+                let text = self.copy_text(log);
+                ui.output_mut(|output| output.copied_text = text);
+            }
         });
-        text.flatten(&mut flattener);
-        job
+        ui.separator();
+
+        self.update_matches(log);
+        let first = log.total_pushed() - log.len() as u64;
+        let row_height = ui.fonts(|fonts| fonts.row_height(&self.format.font_id));
+        ScrollArea::both()
+            .auto_shrink([false, false])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, self.matches.len(), |ui, rows| {
+                for row in rows {
+                    let Some(entry) = log.get((self.matches[row] - first) as usize) else {
+                        continue;
+                    };
+                    ui.horizontal(|ui| {
+                        if self.show_time {
+                            ui.label(RichText::new(utc_time(entry.time)).weak().monospace());
+                        }
+                        let color = match entry.severity {
+                            Severity::Error => ui.visuals().error_fg_color,
+                            Severity::Warn => ui.visuals().warn_fg_color,
+                            Severity::Info => ui.visuals().text_color(),
+                            Severity::Debug | Severity::Trace => ui.visuals().weak_text_color(),
+                        };
+                        ui.label(
+                            RichText::new(format!("{:<5}", entry.severity.as_str()))
+                                .color(color)
+                                .monospace(),
+                        );
+                        let galley = ui.fonts(|fonts| {
+                            self.galleys
+                                .get(fonts, &self.format, &entry.message, f32::INFINITY)
+                        });
+                        ui.add(Label::new(galley).wrap(false));
+                    });
+                }
+            });
+        self.galleys.end_frame();
+    }
+
+    /// Formats the entries which pass the filter as plain text, one per line.
+    fn copy_text(&mut self, log: &MessageLog) -> String {
+        let show_time = self.show_time;
+        let mut text = String::new();
+        for entry in self.matching(log) {
+            if show_time {
+                text.push_str(&utc_time(entry.time));
+                text.push(' ');
+            }
+            let _ = writeln!(text, "{:<5} {}", entry.severity.as_str(), entry.message);
+        }
+        text
+    }
+
+    /// Brings `matches` up to date with the entries in `log`.
+    fn update_matches(&mut self, log: &MessageLog) {
+        let total = log.total_pushed();
+        let first = total - log.len() as u64;
+        let settings = (self.filter.to_lowercase(), self.min_severity);
+        // a different log may have been passed in, which had fewer entries added
+        if self.scanned_with.as_ref() != Some(&settings) || self.scanned > total {
+            self.matches.clear();
+            self.scanned = first;
+        }
+
+        for id in self.scanned.max(first)..total {
+            let Some(entry) = log.get((id - first) as usize) else {
+                continue;
+            };
+            if entry.severity >= settings.1
+                && (settings.0.is_empty()
+                    || entry
+                        .message
+                        .to_string()
+                        .to_lowercase()
+                        .contains(&settings.0))
+            {
+                self.matches.push_back(id);
+            }
+        }
+        while self.matches.front().is_some_and(|id| *id < first) {
+            self.matches.pop_front();
+        }
+        self.scanned = total;
+        self.scanned_with = Some(settings);
+    }
+}
+
+/// Style given to text inserted at the character index `at`.
+fn insert_style(styles: &[MessageStyle], at: usize) -> MessageStyle {
+    // the character before `at`, or the first character if inserting at the start
+    styles
+        .get(at.saturating_sub(1))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Computes the style of each character in `new`, given the style of each character in `old`,
+/// assuming that `new` was created from `old` by replacing a single range of text.
+fn adjust_styles(
+    old: &str,
+    styles: &[MessageStyle],
+    new: &str,
+    pending: Option<MessageStyle>,
+) -> Vec<MessageStyle> {
+    if old == new {
+        return styles.to_vec();
+    }
+
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let inserted = new.len() - prefix - suffix;
+    let style = pending.unwrap_or_else(|| insert_style(styles, prefix));
+
+    let mut adjusted = Vec::with_capacity(new.len());
+    adjusted.extend_from_slice(&styles[..prefix]);
+    adjusted.extend(std::iter::repeat(style).take(inserted));
+    adjusted.extend_from_slice(&styles[old.len() - suffix..]);
+    adjusted
+}
+
+/// Splits `text` into runs of characters with the same style, given the style of each
+/// character.
+fn runs<'a>(text: &'a str, styles: &[MessageStyle]) -> Vec<(&'a str, MessageStyle)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().zip(styles).peekable();
+    while let Some(((_, _), style)) = chars.next() {
+        match chars.peek() {
+            Some(((next, _), next_style)) if next_style != &style => {
+                runs.push((&text[start..*next], *style));
+                start = *next;
+            }
+            Some(_) => {}
+            None => runs.push((&text[start..], *style)),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use egui::{
+        text::Fonts, CentralPanel, Context, Event, FontDefinitions, FontId, Pos2, RawInput, Rect,
+        Stroke, TextFormat, Vec2,
+    };
+
+    use crate::{
+        console::{MessageLog, Severity},
+        Color32, FontFamily, FontSize, IntoMessage, Message, MessageStyle, Styleable,
+    };
+
+    use super::{
+        adjust_styles, CacheStats, ConsoleView, GalleyCache, LossyFields, MessageEditor,
+        MessageHit, MessageLabel, StyleToFormat,
+    };
+
+    #[test]
+    fn sources() {
+        let msg = "ab"
+            .with(Message::default().with("c".bold()))
+            .with("de".color(Color32::RED));
+
+        let (job, sources) = StyleToFormat::default().to_job_with_sources(&msg);
+        assert_eq!("abcde", job.text);
+        assert_eq!(
+            vec![(vec![], 0..2), (vec![0, 0], 2..3), (vec![1], 3..5)],
+            sources
+                .into_iter()
+                .map(|source| (source.path, source.chars))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn console_filter() {
+        let lines = |view: &mut ConsoleView, log: &MessageLog| -> Vec<String> {
+            view.matching(log)
+                .into_iter()
+                .map(|entry| entry.message.to_string())
+                .collect()
+        };
+        let mut log = MessageLog::new(3);
+        let mut view = ConsoleView::default();
+        log.push(Severity::Info, "Player joined");
+        log.push(Severity::Warn, "Player".bold().with(" lagging"));
+        log.push(Severity::Debug, "tick");
+        view.set_filter("player");
+        assert_eq!(
+            vec!["Player joined", "Player lagging"],
+            lines(&mut view, &log)
+        );
+
+        // only new entries are checked, and evicted entries are dropped
+        log.push(Severity::Error, "player left");
+        assert_eq!(
+            vec!["Player lagging", "player left"],
+            lines(&mut view, &log)
+        );
+
+        view.set_min_severity(Severity::Warn);
+        view.set_filter("");
+        assert_eq!(
+            vec!["Player lagging", "player left"],
+            lines(&mut view, &log)
+        );
+
+        log.clear();
+        assert!(lines(&mut view, &log).is_empty());
+    }
+
+    #[test]
+    fn console_show() {
+        let mut log = MessageLog::new(50_000);
+        for i in 0..50_000 {
+            log.push(Severity::Info, "line ".with(i.to_string().bold()));
+        }
+        let mut view = ConsoleView::default();
+        let ctx = Context::default();
+        for _ in 0..2 {
+            let input = RawInput {
+                screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0))),
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                CentralPanel::default().show(ctx, |ui| view.show(ui, &log));
+            });
+        }
+        // only the visible rows were laid out
+        assert!(view.galleys.stats().misses < 200);
+    }
+
+    #[test]
+    fn hover() {
+        let msg = "a".with("b".bold());
+        let ctx = Context::default();
+
+        let mut hovered = Vec::new();
+        for x in [0.5, 10.5, 200.0] {
+            let input = RawInput {
+                events: vec![Event::PointerMoved(Pos2::new(x, 0.5))],
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                CentralPanel::default()
+                    .frame(egui::Frame::none())
+                    .show(ctx, |ui| {
+                        hovered.push(MessageLabel::new(&msg).show(ui).hovered);
+                    });
+            });
+        }
+
+        assert_eq!(
+            vec![
+                Some(MessageHit {
+                    path: vec![],
+                    char_index: 0,
+                    node_chars: 0..1,
+                }),
+                Some(MessageHit {
+                    path: vec![0],
+                    char_index: 1,
+                    node_chars: 1..2,
+                }),
+                None,
+            ],
+            hovered,
+        );
+    }
+
+    #[test]
+    fn from_job_bold() {
+        let msg = "regular "
+            .with("bold ".bold())
+            .with("red".color(Color32::RED));
+        for (style_to_format, ambiguous) in [
+            (StyleToFormat::default(), true),
+            (
+                StyleToFormat {
+                    bold_font_id: Some(FontId::monospace(14.0)),
+                    ..Default::default()
+                },
+                false,
+            ),
+        ] {
+            let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+            assert_eq!(msg.normalized(), converted);
+            assert_eq!(
+                LossyFields {
+                    bold: ambiguous,
+                    ..Default::default()
+                },
+                lossy,
+            );
+        }
+    }
+
+    #[test]
+    fn from_job_white() {
+        let msg = "white".color(Color32::WHITE);
+
+        // the same color as bold text can't be told apart from it
+        let style_to_format = StyleToFormat::default();
+        let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+        assert_eq!("white".bold(), converted);
+        assert!(lossy.bold);
+
+        let style_to_format = StyleToFormat {
+            bold_color: None,
+            ..Default::default()
+        };
+        let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+        assert_eq!(msg, converted);
+        assert!(lossy.is_lossless());
+    }
+
+    #[test]
+    fn bold_distinct() {
+        let job = StyleToFormat::default().to_job(&"regular ".with("bold".bold()));
+        assert_ne!(job.sections[0].format, job.sections[1].format);
+    }
+
+    #[test]
+    fn from_job_bold_same_family() {
+        // the bold font only differs in size, so it can't be told apart from a size override
+        let style_to_format = StyleToFormat {
+            bold_font_id: Some(FontId::proportional(20.0)),
+            bold_color: None,
+            ..Default::default()
+        };
+        let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&"bold".bold()));
+        assert_eq!("bold".size(FontSize::Points(20.0)), converted);
+        assert!(lossy.bold);
+    }
+
+    #[test]
+    fn from_job_bold_family_override() {
+        let style_to_format = StyleToFormat {
+            bold_font_id: Some(FontId::new(14.0, egui::FontFamily::Name("bold".into()))),
+            bold_color: None,
+            ..Default::default()
+        };
+        let msg = "code".bold().family(FontFamily::Monospace);
+        let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+        assert_eq!("code".family(FontFamily::Monospace), converted);
+        assert!(lossy.bold);
+
+        // with a bold color, the text can still be read back as bold
+        let style_to_format = StyleToFormat {
+            bold_color: Some(Color32::WHITE),
+            ..style_to_format
+        };
+        let (converted, lossy) = style_to_format.from_job(&style_to_format.to_job(&msg));
+        assert_eq!(msg, converted);
+        assert!(lossy.bold);
+    }
+
+    #[test]
+    fn from_job_lossy() {
+        let style_to_format = StyleToFormat::default();
+        let format = TextFormat {
+            font_id: FontId::new(14.0, egui::FontFamily::Name("fancy".into())),
+            underline: Stroke::new(3.0, Color32::GREEN),
+            ..style_to_format.to_format(MessageStyle::default())
+        };
+
+        let (style, lossy) = style_to_format.from_format(&format);
+        assert_eq!(MessageStyle::new().underline(), style);
+        assert_eq!(
+            LossyFields {
+                font: true,
+                stroke: true,
+                ..Default::default()
+            },
+            lossy,
+        );
+    }
+
+    #[test]
+    fn size_and_family() {
+        let style_to_format = StyleToFormat {
+            font_id: FontId::proportional(10.0),
+            bold_font_id: Some(FontId::new(12.0, egui::FontFamily::Name("bold".into()))),
+            ..Default::default()
+        };
+
+        let style = MessageStyle::new()
+            .size(FontSize::Scale(2.0))
+            .family(FontFamily::Monospace);
+        assert_eq!(
+            FontId::monospace(20.0),
+            style_to_format.to_format(style).font_id,
+        );
+
+        let bold = MessageStyle::new().bold().size(FontSize::Scale(2.0));
+        let format = style_to_format.to_format(bold);
+        assert_eq!(24.0, format.font_id.size);
+        assert_eq!(
+            (
+                MessageStyle::new().bold().size(FontSize::Points(24.0)),
+                LossyFields::default(),
+            ),
+            style_to_format.from_format(&format),
+        );
+    }
+
+    fn fonts() -> Fonts {
+        Fonts::new(1.0, 1024, FontDefinitions::default())
+    }
+
+    #[test]
+    fn galley_cache() {
+        let fonts = fonts();
+        let style_to_format = StyleToFormat::default();
+        let mut cache = GalleyCache::default();
+        let msg = "a".italic();
+
+        let galley = cache.get(&fonts, &style_to_format, &msg, f32::INFINITY);
+        let cached = cache.get(&fonts, &style_to_format, &msg, f32::INFINITY);
+        assert!(Arc::ptr_eq(&galley, &cached));
+        let red = StyleToFormat {
+            default_color: Color32::RED,
+            ..Default::default()
+        };
+        assert!(!Arc::ptr_eq(
+            &galley,
+            &cache.get(&fonts, &red, &msg, f32::INFINITY)
+        ));
+        assert!(!Arc::ptr_eq(
+            &galley,
+            &cache.get(&fonts, &style_to_format, &msg, 100.0)
+        ));
+        assert_eq!(
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                evictions: 0,
+                len: 3,
+            },
+            cache.stats(),
+        );
+    }
+
+    #[test]
+    fn galley_cache_capacity() {
+        let fonts = fonts();
+        let style_to_format = StyleToFormat::default();
+        let get = |cache: &mut GalleyCache, msg: &Message| {
+            cache.get(&fonts, &style_to_format, msg, f32::INFINITY)
+        };
+        let mut cache = GalleyCache {
+            capacity: 2,
+            ..Default::default()
+        };
+        let [a, b, c] = ["a", "b", "c"].map(IntoMessage::into_text);
+
+        get(&mut cache, &a);
+        get(&mut cache, &b);
+        // `b` is now the least recently used, so it is evicted for `c`
+        get(&mut cache, &a);
+        get(&mut cache, &c);
+        assert_eq!((1, 2), (cache.stats().evictions, cache.stats().len));
+
+        cache.reset_stats();
+        get(&mut cache, &a);
+        get(&mut cache, &c);
+        assert_eq!((2, 0), (cache.stats().hits, cache.stats().misses));
+        get(&mut cache, &b);
+        assert_eq!((1, 1), (cache.stats().misses, cache.stats().evictions));
+    }
+
+    #[test]
+    fn galley_cache_age() {
+        let fonts = fonts();
+        let style_to_format = StyleToFormat::default();
+        let get = |cache: &mut GalleyCache, msg: &Message| {
+            cache.get(&fonts, &style_to_format, msg, f32::INFINITY)
+        };
+        let mut cache = GalleyCache {
+            max_age: 2,
+            ..Default::default()
+        };
+        let [a, b] = ["a", "b"].map(IntoMessage::into_text);
+
+        get(&mut cache, &a);
+        cache.end_frame();
+        get(&mut cache, &b);
+        cache.end_frame();
+        assert_eq!(2, cache.stats().len);
+
+        // `a` has not been used for 2 frames, but `b` has only been unused for 1
+        cache.end_frame();
+        assert_eq!((1, 1), (cache.stats().evictions, cache.stats().len));
+        get(&mut cache, &b);
+        assert_eq!(1, cache.stats().hits);
+    }
+
+    #[test]
+    fn adjust() {
+        let bold = MessageStyle::new().bold();
+        let none = MessageStyle::new();
+        let styles = [none, none, bold, bold, none];
+
+        // typing after a bold character continues the bold style
+        assert_eq!(
+            vec![none, none, bold, bold, bold, none],
+            adjust_styles("abcde", &styles, "abcdXe", None),
+        );
+        // deleting
+        assert_eq!(
+            vec![none, bold, bold, none],
+            adjust_styles("abcde", &styles, "acde", None),
+        );
+        // replacing with a pending style
+        let italic = MessageStyle::new().italic();
+        assert_eq!(
+            vec![none, italic, italic, none],
+            adjust_styles("abcde", &styles, "aXYe", Some(italic)),
+        );
+    }
+
+    #[test]
+    fn toggle() {
+        let mut editor = MessageEditor::new(&"one ".with("two".bold()));
+        editor.selection = 0..7;
+        editor.toggle_bold();
+        assert_eq!("one two".bold(), editor.message());
+
+        editor.toggle_bold();
+        assert_eq!("one two".no_bold(), editor.message());
+
+        editor.selection = 4..7;
+        editor.set_color(Some(Color32::RED));
+        editor.toggle_italic();
+        assert_eq!(
+            Message::default()
+                .with("one ".no_bold())
+                .with("two".no_bold().italic().color(Color32::RED)),
+            editor.message(),
+        );
     }
 }
@@ -0,0 +1,477 @@
+//! Functions for laying out messages in fixed-width outputs such as terminals, where each
+//! character takes up the number of columns given by its display width.
+//!
+//! Display widths are measured using [`unicode_width`], so wide characters such as CJK take up
+//! two columns, and control characters take up none.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{layout::{Alignment, Fill}, Color32, IntoMessage, MessageStyle, Styleable};
+//!
+//! let fill = Fill::new('=', MessageStyle::new().color(Color32::GRAY));
+//! let title = "Scores"
+//!     .bold()
+//!     .align(8, Alignment::Center)
+//!     .pad(12, Alignment::Center, fill);
+//! assert_eq!("== Scores ==", title.to_string());
+//!
+//! let score = "85".into_text().align(5, Alignment::Right);
+//! assert_eq!("   85", score.to_string());
+//!
+//! let text = "the quick brown fox jumps over the lazy dog".into_text();
+//! let lines: Vec<_> = text.justify(16, ' ').iter().map(ToString::to_string).collect();
+//! assert_eq!(vec!["the  quick brown", "fox  jumps  over", "the lazy dog"], lines);
+//! ```
+
+use std::mem;
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::{Message, MessageStyle};
+
+/// Horizontal position of content within a wider space.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    /// Content starts at the left edge.
+    #[default]
+    Left,
+    /// Content is centered, with any odd column of space on the right.
+    Center,
+    /// Content ends at the right edge.
+    Right,
+}
+
+impl Alignment {
+    /// Splits `extra` columns of space into the columns before and after content aligned this
+    /// way.
+    pub(crate) const fn split(self, extra: usize) -> (usize, usize) {
+        match self {
+            Self::Left => (0, extra),
+            Self::Center => (extra / 2, extra - extra / 2),
+            Self::Right => (extra, 0),
+        }
+    }
+}
+
+/// A character used to fill space when padding or justifying a [`Message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fill {
+    /// The character repeated to fill space.
+    pub ch: char,
+    /// Style of the fill characters.
+    pub style: MessageStyle,
+}
+
+impl Default for Fill {
+    /// Unstyled spaces.
+    fn default() -> Self {
+        Self::from(' ')
+    }
+}
+
+impl From<char> for Fill {
+    /// Unstyled `ch` characters.
+    fn from(ch: char) -> Self {
+        Self::new(ch, MessageStyle::new())
+    }
+}
+
+impl Fill {
+    /// Creates a fill of `ch` characters with the given style.
+    #[must_use]
+    pub const fn new(ch: char, style: MessageStyle) -> Self {
+        Self { ch, style }
+    }
+
+    /// Appends fill characters taking up `width` columns to `line`.
+    ///
+    /// If the width of the fill character does not divide `width`, the rest is filled with
+    /// spaces in the same style.
+    fn push_to(self, line: &mut Line, width: usize) {
+        let ch_width = char_width(self.ch);
+        let (count, rest) = width
+            .checked_div(ch_width)
+            .map_or((0, width), |count| (count, width % ch_width));
+        let mut fill: String = std::iter::repeat(self.ch).take(count).collect();
+        fill.extend(std::iter::repeat(' ').take(rest));
+        push_run(line, &fill, self.style);
+    }
+}
+
+impl Message {
+    /// Gets the number of columns taken up by the widest line of this message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// assert_eq!(5, "Hello".into_text().display_width());
+    /// assert_eq!(4, "日本".with("\nab".bold()).with("c\n").display_width());
+    /// ```
+    #[must_use]
+    pub fn display_width(&self) -> usize {
+        let mut widest = 0;
+        let mut width = 0;
+        for (content, _) in self.segments() {
+            for c in content.chars() {
+                if c == '\n' {
+                    width = 0;
+                } else {
+                    width += char_width(c);
+                    widest = widest.max(width);
+                }
+            }
+        }
+        widest
+    }
+
+    /// Pads each line of this message with unstyled spaces, so that it takes up `width`
+    /// columns with the given alignment.
+    ///
+    /// This is the same as [`Message::pad`] with [`Fill::default`].
+    #[must_use]
+    pub fn align(&self, width: usize, align: Alignment) -> Self {
+        self.pad(width, align, Fill::default())
+    }
+
+    /// Pads each line of this message with `fill`, so that it takes up `width` columns with the
+    /// given alignment.
+    ///
+    /// Lines which are already wider than `width` are left as they are. The styles of the
+    /// original content are kept, but the node hierarchy is flattened as in
+    /// [`Message::normalized`].
+    #[must_use]
+    pub fn pad(&self, width: usize, align: Alignment, fill: impl Into<Fill>) -> Self {
+        let fill = fill.into();
+        let lines = wrap_lines(self, usize::MAX).into_iter().map(|(line, _)| {
+            let (before, after) = align.split(width.saturating_sub(line_width(&line)));
+            let mut padded = Line::new();
+            fill.push_to(&mut padded, before);
+            for (content, style) in line {
+                push_run(&mut padded, &content, style);
+            }
+            fill.push_to(&mut padded, after);
+            padded
+        });
+        join_lines(lines)
+    }
+
+    /// Splits this message into lines at line breaks, then wraps each line at word boundaries
+    /// so that it takes up at most `width` columns.
+    ///
+    /// Whitespace at the point where a line is wrapped is removed, and words wider than `width`
+    /// are broken between characters. The returned lines have no line breaks, and there is
+    /// always at least one line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let msg = "Press ".with("Enter".bold()).with(" to continue");
+    /// let lines: Vec<_> = msg.wrap(12).iter().map(ToString::to_string).collect();
+    /// assert_eq!(vec!["Press Enter", "to continue"], lines);
+    /// ```
+    #[must_use]
+    pub fn wrap(&self, width: usize) -> Vec<Self> {
+        wrap(self, width).iter().map(to_message).collect()
+    }
+
+    /// Like [`Message::wrap`], but also inserts `fill` characters between words so that each
+    /// wrapped line takes up exactly `width` columns.
+    ///
+    /// The last line of each paragraph, which ends at a line break or the end of the message,
+    /// is not justified. Neither are lines with a single word, or whitespace before their
+    /// first word. The extra space is spread as evenly as possible between the words, with
+    /// any remainder going to the gaps on the left.
+    #[must_use]
+    pub fn justify(&self, width: usize, fill: impl Into<Fill>) -> Vec<Self> {
+        let fill = fill.into();
+        wrap_lines(self, width)
+            .into_iter()
+            .map(|(line, wrapped)| {
+                if wrapped {
+                    to_message(&justify(&line, width, fill))
+                } else {
+                    to_message(&line)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Inserts `fill` after each gap between words in `line`, so that it takes up `width` columns.
+fn justify(line: &Line, width: usize, fill: Fill) -> Line {
+    let chars: Vec<(char, MessageStyle)> = line
+        .iter()
+        .flat_map(|(content, style)| content.chars().map(|c| (c, *style)))
+        .collect();
+    // index of the first character of each word after the first
+    let mut gap_ends = Vec::new();
+    let mut seen_word = false;
+    for (index, (c, _)) in chars.iter().enumerate() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let after_space = index > 0 && chars[index - 1].0.is_whitespace();
+        if seen_word && after_space {
+            gap_ends.push(index);
+        }
+        seen_word = true;
+    }
+
+    let extra = width.saturating_sub(line_width(line));
+    if gap_ends.is_empty() || extra == 0 {
+        return line.clone();
+    }
+    let (per_gap, rest) = (extra / gap_ends.len(), extra % gap_ends.len());
+    let mut justified = Line::new();
+    let mut gaps = gap_ends.iter().enumerate().peekable();
+    for (index, (c, style)) in chars.into_iter().enumerate() {
+        if let Some((gap, _)) = gaps.next_if(|(_, end)| **end == index) {
+            fill.push_to(&mut justified, per_gap + usize::from(gap < rest));
+        }
+        push_run(&mut justified, c.encode_utf8(&mut [0; 4]), style);
+    }
+    justified
+}
+
+/// Converts a line back into a message.
+pub(crate) fn to_message(line: &Line) -> Message {
+    Message::from_segments(line.iter().map(|(content, style)| (content, *style)))
+}
+
+/// Joins lines into a single message, separated by unstyled line breaks.
+fn join_lines(lines: impl IntoIterator<Item = Line>) -> Message {
+    let mut joined = Line::new();
+    for (index, line) in lines.into_iter().enumerate() {
+        if index > 0 {
+            push_run(&mut joined, "\n", MessageStyle::new());
+        }
+        for (content, style) in line {
+            push_run(&mut joined, &content, style);
+        }
+    }
+    to_message(&joined)
+}
+
+/// A single line of styled text, as runs of content with the same style.
+pub(crate) type Line = Vec<(String, MessageStyle)>;
+
+/// Number of columns taken up by `c`, where control characters take up none.
+pub(crate) fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Number of columns taken up by `line`.
+pub(crate) fn line_width(line: &Line) -> usize {
+    line.iter()
+        .flat_map(|(content, _)| content.chars())
+        .map(char_width)
+        .sum()
+}
+
+/// Appends `content` to `line`, extending the last run if it has the same style.
+pub(crate) fn push_run(line: &mut Line, content: &str, style: MessageStyle) {
+    if content.is_empty() {
+        return;
+    }
+    match line.last_mut() {
+        Some((last, last_style)) if *last_style == style => last.push_str(content),
+        _ => line.push((content.to_owned(), style)),
+    }
+}
+
+/// Splits `message` into lines at line breaks, then wraps each line at word boundaries so that
+/// it takes up at most `width` columns.
+///
+/// Whitespace at the point where a line is wrapped is removed, and words wider than `width` are
+/// broken between characters. There is always at least one line.
+pub(crate) fn wrap(message: &Message, width: usize) -> Vec<Line> {
+    wrap_lines(message, width)
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// Like [`wrap`], but also returns whether each line was wrapped, rather than ending at a line
+/// break or the end of the message.
+fn wrap_lines(message: &Message, width: usize) -> Vec<(Line, bool)> {
+    let mut wrapper = Wrapper {
+        width: width.max(1),
+        lines: Vec::new(),
+        line: Line::new(),
+        line_width: 0,
+        wrapped: false,
+        spaces: Vec::new(),
+        word: Vec::new(),
+    };
+    for (content, style) in message.segments() {
+        for c in content.chars() {
+            wrapper.push(c, style);
+        }
+    }
+    wrapper.finish()
+}
+
+struct Wrapper {
+    width: usize,
+    lines: Vec<(Line, bool)>,
+    line: Line,
+    line_width: usize,
+    /// Whether `line` continues a line which was wrapped.
+    wrapped: bool,
+    /// Whitespace before `word`.
+    spaces: Vec<(char, MessageStyle)>,
+    word: Vec<(char, MessageStyle)>,
+}
+
+impl Wrapper {
+    fn push(&mut self, c: char, style: MessageStyle) {
+        if c == '\n' {
+            self.end_word();
+            self.break_line(false);
+        } else if c.is_whitespace() {
+            if !self.word.is_empty() {
+                self.end_word();
+            }
+            self.spaces.push((c, style));
+        } else {
+            self.word.push((c, style));
+        }
+    }
+
+    /// Places the pending whitespace and word on the current line, or on a new line if they do
+    /// not fit.
+    fn end_word(&mut self) {
+        let spaces = mem::take(&mut self.spaces);
+        let word = mem::take(&mut self.word);
+        let width = |chars: &[(char, MessageStyle)]| -> usize {
+            chars.iter().map(|(c, _)| char_width(*c)).sum()
+        };
+        if word.is_empty() {
+            // trailing whitespace is kept only if it fits
+            if (self.line_width > 0 || !self.wrapped)
+                && self.line_width + width(&spaces) <= self.width
+            {
+                for (c, style) in spaces {
+                    self.push_char(c, style);
+                }
+            }
+            return;
+        }
+
+        if self.line_width > 0 && self.line_width + width(&spaces) + width(&word) > self.width {
+            self.break_line(true);
+        } else if self.line_width > 0 || !self.wrapped {
+            for (c, style) in spaces {
+                self.push_char(c, style);
+            }
+        }
+        for (c, style) in word {
+            self.push_char(c, style);
+        }
+    }
+
+    fn push_char(&mut self, c: char, style: MessageStyle) {
+        let width = char_width(c);
+        if self.line_width > 0 && self.line_width + width > self.width {
+            self.break_line(true);
+        }
+        push_run(&mut self.line, c.encode_utf8(&mut [0; 4]), style);
+        self.line_width += width;
+    }
+
+    fn break_line(&mut self, wrapped: bool) {
+        self.lines.push((mem::take(&mut self.line), wrapped));
+        self.line_width = 0;
+        self.wrapped = wrapped;
+    }
+
+    fn finish(mut self) -> Vec<(Line, bool)> {
+        self.end_word();
+        self.lines.push((self.line, false));
+        self.lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color32, IntoMessage, Message, MessageStyle, Styleable};
+
+    use super::{line_width, wrap, Alignment, Fill, Line};
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|(content, _)| content.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wrap_words() {
+        let msg = "the quick ".with("brown".bold()).with(" fox\n  jumps");
+        let lines = wrap(&msg, 10);
+        assert_eq!(vec!["the quick", "brown fox", "  jumps"], text(&lines));
+        assert_eq!(
+            vec![
+                ("brown".to_owned(), MessageStyle::new().bold()),
+                (" fox".to_owned(), MessageStyle::new()),
+            ],
+            lines[1],
+        );
+
+        assert_eq!(
+            vec!["abcd", "ef g", "h"],
+            text(&wrap(&"abcdef g h".into_text(), 4))
+        );
+        assert_eq!(vec![""], text(&wrap(&"".into_text(), 4)));
+    }
+
+    #[test]
+    fn wide_chars() {
+        let lines = wrap(&"日本語の文".into_text(), 5);
+        assert_eq!(vec!["日本", "語の", "文"], text(&lines));
+        assert_eq!(4, line_width(&lines[0]));
+    }
+
+    #[test]
+    fn pad() {
+        let fill = Fill::new('·', MessageStyle::new().color(Color32::GRAY));
+        let msg = Message::default()
+            .with("ab".bold())
+            .with("\nc")
+            .pad(5, Alignment::Right, fill);
+        assert_eq!(
+            Message::from_segments([
+                ("···", fill.style),
+                ("ab", MessageStyle::new().bold()),
+                ("\n", MessageStyle::new()),
+                ("····", fill.style),
+                ("c", MessageStyle::new()),
+            ]),
+            msg,
+        );
+
+        // wide fill characters which do not fit are replaced with spaces
+        let msg = "ab".into_text().pad(5, Alignment::Right, '日');
+        assert_eq!("日 ab", msg.to_string());
+        let msg = "too long".into_text().align(3, Alignment::Center);
+        assert_eq!("too long", msg.to_string());
+    }
+
+    #[test]
+    fn justify() {
+        let msg = "a bb".with(" c".underline()).with("\nd e fff g");
+        let lines = |width| -> Vec<String> {
+            msg.justify(width, '_')
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(vec!["a bb c", "d _e fff", "g"], lines(8));
+        assert_eq!(vec!["a _bb", "c", "d __e", "fff g"], lines(5));
+    }
+}
@@ -29,17 +29,42 @@
 //! See the documentation of [`Message`] for usage info.
 //!
 //! ## Feature flags
-#![cfg_attr(feature = "document_features", doc = document_features::document_features!())]
+#![cfg_attr(feature = "document-features", doc = document_features::document_features!())]
 //!
 //! [`Message`]: crate::Message
 
+pub mod accessible;
+#[cfg(any(feature = "ratatui", feature = "crossterm"))]
+pub mod ansi;
+#[cfg(feature = "bevy")]
+pub mod bevy;
+pub mod binary;
+pub mod console;
+#[cfg(feature = "cosmic-text")]
+pub mod cosmic_text;
+#[cfg(feature = "crossterm")]
+pub mod crossterm;
 #[cfg(feature = "egui")]
 pub mod egui;
+pub mod layout;
+#[cfg(feature = "log")]
+pub mod log;
+#[cfg(feature = "ratatui")]
+pub mod ratatui;
+pub mod render;
+pub mod sanitize;
+pub mod search;
+pub mod table;
 #[cfg(feature = "termcolor")]
 pub mod termcolor;
 pub mod text;
+#[cfg(feature = "tracing")]
+pub mod tracing;
 pub mod util;
 
 pub use ecolor::Color32;
-pub use text::{IntoMessage, Message, MessageStyle, Styleable};
-pub use util::{MessageFlattener, StackFlattener};
+pub use text::{FontFamily, FontSize, IntoMessage, Message, MessageStyle, Styleable};
+pub use util::{
+    MessageFlattener, MessageVisitorMut, NodeContext, NodeStackFlattener, StackFlattener,
+    TryMessageFlattener, TryStackFlattener,
+};
@@ -0,0 +1,266 @@
+//! Features for writing [`log`] records as text messages to the terminal, using
+//! [`termcolor`].
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{log::MessageLogger, Color32, IntoMessage, Styleable};
+//! use log::LevelFilter;
+//!
+//! MessageLogger::new()
+//!     .level(LevelFilter::Debug)
+//!     .format(|record| {
+//!         format!("[{}] ", record.level())
+//!             .color(Color32::GRAY)
+//!             .with(record.args().to_string())
+//!     })
+//!     .init()
+//!     .unwrap();
+//!
+//! log::info!("server started");
+//! ```
+
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use termcolor::{ColorChoice, StandardStream, WriteColor};
+
+use crate::{
+    console::{utc_time, Severity},
+    Color32, IntoMessage, Message, MessageStyle, Styleable,
+};
+
+type FormatFn = dyn Fn(&Record<'_>) -> Message + Send + Sync;
+
+/// [`Log`] implementation which formats records as [`Message`]s, and writes them to a stream
+/// using [`Message::write`].
+///
+/// By default, records are formatted using [`default_format`] and written to standard error,
+/// and colors are used if standard error is a terminal which supports them. Another stream can
+/// be set with [`MessageLogger::stream`].
+pub struct MessageLogger<W = StandardStream> {
+    level: LevelFilter,
+    format: Box<FormatFn>,
+    stream: Mutex<W>,
+}
+
+impl<W> fmt::Debug for MessageLogger<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageLogger")
+            .field("level", &self.level)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for MessageLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageLogger {
+    /// Creates a logger for all records at [`LevelFilter::Info`] or above.
+    #[must_use]
+    pub fn new() -> Self {
+        let choice = if io::stderr().is_terminal() {
+            ColorChoice::Auto
+        } else {
+            ColorChoice::Never
+        };
+        Self {
+            level: LevelFilter::Info,
+            format: Box::new(default_format),
+            stream: Mutex::new(StandardStream::stderr(choice)),
+        }
+    }
+
+    /// Sets whether colors are used, overriding the detection of whether standard error is a
+    /// terminal.
+    #[must_use]
+    pub fn color_choice(mut self, choice: ColorChoice) -> Self {
+        self.stream = Mutex::new(StandardStream::stderr(choice));
+        self
+    }
+}
+
+impl<W> MessageLogger<W> {
+    /// Sets the most verbose level of records which are logged.
+    #[must_use]
+    pub const fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the function which formats each record into a message.
+    #[must_use]
+    pub fn format<F>(mut self, format: F) -> Self
+    where
+        F: Fn(&Record<'_>) -> Message + Send + Sync + 'static,
+    {
+        self.format = Box::new(format);
+        self
+    }
+
+    /// Sets the stream which records are written to instead of standard error, which decides
+    /// itself whether colors are used.
+    #[must_use]
+    pub fn stream<S: WriteColor>(self, stream: S) -> MessageLogger<S> {
+        MessageLogger {
+            level: self.level,
+            format: self.format,
+            stream: Mutex::new(stream),
+        }
+    }
+
+    /// Gets the stream which records are written to.
+    pub fn into_inner(self) -> W {
+        // a panic while writing a record cannot leave the stream in an invalid state
+        self.stream
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets this as the global logger, and sets the global maximum level to this logger's
+    /// level.
+    ///
+    /// # Errors
+    ///
+    /// Errors if a global logger has already been set.
+    pub fn init(self) -> Result<(), SetLoggerError>
+    where
+        W: WriteColor + Send + 'static,
+    {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<W: WriteColor + Send> Log for MessageLogger<W> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = (self.format)(record);
+        let mut stream = self.stream.lock().unwrap_or_else(|err| err.into_inner());
+        // errors from writing a log cannot be reported anywhere
+        let _ = message.write(&mut *stream);
+        let _ = stream.write_all(b"\n");
+    }
+
+    fn flush(&self) {
+        let mut stream = self.stream.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = stream.flush();
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => Self::Error,
+            Level::Warn => Self::Warn,
+            Level::Info => Self::Info,
+            Level::Debug => Self::Debug,
+            Level::Trace => Self::Trace,
+        }
+    }
+}
+
+/// Gets the style of the level of a record in [`default_format`].
+#[must_use]
+pub fn level_style(level: Level) -> MessageStyle {
+    Severity::from(level).style()
+}
+
+/// Formats a record as its UTC time, colored level, bold target and message.
+///
+/// ```text
+/// 12:34:56.789 INFO my_app::server: server started
+/// ```
+pub fn default_format(record: &Record<'_>) -> Message {
+    Message::default()
+        .with(utc_time(SystemTime::now()).color(Color32::DARK_GRAY))
+        .with(" ")
+        .with(
+            format!("{:<5}", record.level())
+                .into_text()
+                .with_style(level_style(record.level())),
+        )
+        .with(" ")
+        .with(format!("{}:", record.target()).bold())
+        .with(" ")
+        .with(record.args().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use termcolor::Ansi;
+
+    use super::{default_format, level_style, MessageLogger};
+    use crate::{MessageStyle, Styleable};
+
+    fn record(level: Level) -> Record<'static> {
+        Record::builder()
+            .level(level)
+            .target("app::net")
+            .args(format_args!("timed out"))
+            .build()
+    }
+
+    #[test]
+    fn format() {
+        let msg = default_format(&record(Level::Warn));
+        assert!(msg.to_string().ends_with(" WARN  app::net: timed out"));
+
+        let segments = msg
+            .segments()
+            .filter(|(content, _)| !content.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(("WARN ", level_style(Level::Warn)), segments[2]);
+        assert_eq!(("app::net:", MessageStyle::new().bold()), segments[4]);
+        assert_eq!(("timed out", MessageStyle::default()), segments[6]);
+    }
+
+    #[test]
+    fn enabled() {
+        let logger = MessageLogger::new().level(LevelFilter::Warn);
+        let enabled = |level| logger.enabled(&Metadata::builder().level(level).build());
+        assert!(enabled(Level::Error));
+        assert!(enabled(Level::Warn));
+        assert!(!enabled(Level::Info));
+        assert!(!MessageLogger::new()
+            .level(LevelFilter::Off)
+            .enabled(&Metadata::builder().level(Level::Error).build()));
+    }
+
+    #[test]
+    fn log() {
+        let logger = MessageLogger::new()
+            .level(LevelFilter::Warn)
+            .format(|record| record.args().to_string().bold())
+            .stream(Ansi::new(Vec::new()));
+        logger.log(&record(Level::Warn));
+        logger.log(&record(Level::Info));
+        logger.flush();
+
+        let mut expected = Ansi::new(Vec::new());
+        "timed out".bold().write(&mut expected).unwrap();
+        expected.write_all(b"\n").unwrap();
+        let expected = expected.into_inner();
+        assert!(expected.starts_with(b"\x1b[0m\x1b[1m"));
+        assert_eq!(expected, logger.into_inner().into_inner());
+    }
+}
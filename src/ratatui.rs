@@ -0,0 +1,323 @@
+//! Features for converting text messages to and from [`ratatui`] text, used in terminal UIs.
+
+use std::{convert::Infallible, mem};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+};
+
+use crate::{
+    ansi::{self, ColorMode},
+    render::Renderer,
+    Color32, Message, MessageStyle, StackFlattener, Styleable,
+};
+
+/// Defines how to convert a [`MessageStyle`] into a ratatui [`Style`], and back.
+///
+/// Unlike termcolor, a [`Style`] can explicitly remove a modifier, so a decoration set to
+/// `Some(false)` is converted to [`Style::sub_modifier`] rather than being left unset.
+///
+/// [`MessageStyle::size`] and [`MessageStyle::family`] are ignored, as described in
+/// [`FontSize`](crate::FontSize).
+///
+/// # Examples
+///
+/// ```
+/// use expedition::{ratatui::StyleToRatatui, Color32, IntoMessage, Styleable};
+/// use ratatui::{style::{Color, Modifier, Style}, text::{Line, Span}};
+///
+/// let msg = "Status: "
+///     .with("OK\n".color(Color32::GREEN).bold())
+///     .with("All systems nominal");
+///
+/// let text = StyleToRatatui::default().to_text(&msg);
+/// assert_eq!(2, text.lines.len());
+/// assert_eq!(
+///     Line::from(vec![
+///         Span::raw("Status: "),
+///         Span::styled("OK", Style::new().fg(Color::Rgb(0, 255, 0)).add_modifier(Modifier::BOLD)),
+///     ]),
+///     text.lines[0],
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct StyleToRatatui {
+    /// How colors are converted.
+    pub colors: ColorMode,
+}
+
+impl StyleToRatatui {
+    /// Converts a [`MessageStyle`] to a [`Style`].
+    pub fn to_style(&self, style: MessageStyle) -> Style {
+        let mut res = Style::new();
+        res.fg = style.color.map(|color| match self.colors {
+            ColorMode::Rgb => Color::Rgb(color.r(), color.g(), color.b()),
+            ColorMode::Indexed => Color::Indexed(ansi::to_indexed(color)),
+        });
+        for (modifier, state) in decorations(style) {
+            match state {
+                Some(true) => res.add_modifier |= modifier,
+                Some(false) => res.sub_modifier |= modifier,
+                None => {}
+            }
+        }
+        res
+    }
+
+    /// Converts a [`Message`] to a sequence of [`Span`]s, without splitting on newlines.
+    ///
+    /// This uses [`Message::flatten`] to convert from a node hierarchy to a linear sequence of
+    /// spans. Nodes with no content do not create a span.
+    pub fn to_spans(&self, text: &Message) -> Vec<Span<'static>> {
+        let mut spans = Vec::new();
+        let mut flattener = StackFlattener::new(|content, style| {
+            if !content.is_empty() {
+                spans.push(Span::styled(content.to_owned(), self.to_style(style)));
+            }
+        });
+        text.flatten(&mut flattener);
+        spans
+    }
+
+    /// Converts a [`Message`] to a sequence of [`Line`]s, splitting on newlines.
+    ///
+    /// This uses [`Message::render`] with a [`LinesRenderer`].
+    pub fn to_lines(&self, text: &Message) -> Vec<Line<'static>> {
+        let mut renderer = LinesRenderer::new(self);
+        text.render(&mut renderer)
+            .unwrap_or_else(|never| match never {});
+        renderer.finish()
+    }
+
+    /// Converts a [`Message`] to a [`Text`], splitting on newlines.
+    ///
+    /// See [`StyleToRatatui::to_lines`].
+    pub fn to_text(&self, text: &Message) -> Text<'static> {
+        Text::from(self.to_lines(text))
+    }
+
+    /// Converts a [`Style`] back to a [`MessageStyle`], as the inverse of
+    /// [`StyleToRatatui::to_style`].
+    ///
+    /// Named colors and [`Color::Indexed`] colors are converted using the xterm default
+    /// palette. Background colors and modifiers with no equivalent in [`MessageStyle`] are
+    /// ignored.
+    pub fn from_style(&self, style: Style) -> MessageStyle {
+        let state = |modifier| {
+            if style.add_modifier.contains(modifier) {
+                Some(true)
+            } else if style.sub_modifier.contains(modifier) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        MessageStyle {
+            color: style.fg.and_then(from_color),
+            bold: state(Modifier::BOLD),
+            italic: state(Modifier::ITALIC),
+            underline: state(Modifier::UNDERLINED),
+            strikethrough: state(Modifier::CROSSED_OUT),
+            ..Default::default()
+        }
+    }
+
+    /// Converts a [`Span`] back to a [`Message`].
+    pub fn from_span(&self, span: &Span) -> Message {
+        Message::new(span.content.as_ref()).with_style(self.from_style(span.style))
+    }
+
+    /// Converts a [`Line`] back to a [`Message`], in the form given by [`Message::normalized`].
+    pub fn from_line(&self, line: &Line) -> Message {
+        Message::from_segments(
+            line.spans
+                .iter()
+                .map(|span| (span.content.as_ref(), self.from_style(span.style))),
+        )
+    }
+
+    /// Converts a [`Text`] back to a [`Message`], in the form given by
+    /// [`Message::normalized`].
+    ///
+    /// Lines are joined with unstyled newlines.
+    pub fn from_text(&self, text: &Text) -> Message {
+        let mut segments = Vec::new();
+        for (i, line) in text.lines.iter().enumerate() {
+            if i > 0 {
+                segments.push(("\n", MessageStyle::default()));
+            }
+            segments.extend(
+                line.spans
+                    .iter()
+                    .map(|span| (span.content.as_ref(), self.from_style(span.style))),
+            );
+        }
+        Message::from_segments(segments)
+    }
+}
+
+/// Renders [`Message`]s to a sequence of [`Line`]s, using a [`StyleToRatatui`].
+///
+/// Like [`str::lines`], a trailing newline does not create an empty line, and a `\r` before a
+/// newline is removed. Rendering multiple messages continues from the end of the last line.
+#[derive(Debug, Clone)]
+pub struct LinesRenderer<'a> {
+    format: &'a StyleToRatatui,
+    lines: Vec<Line<'static>>,
+    current: Vec<Span<'static>>,
+}
+
+impl<'a> LinesRenderer<'a> {
+    /// Creates a renderer with no lines.
+    pub const fn new(format: &'a StyleToRatatui) -> Self {
+        Self {
+            format,
+            lines: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    /// Gets the lines that have been rendered.
+    pub fn finish(mut self) -> Vec<Line<'static>> {
+        if !self.current.is_empty() {
+            self.lines.push(Line::from(self.current));
+        }
+        self.lines
+    }
+}
+
+impl Renderer for LinesRenderer<'_> {
+    type Error = Infallible;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Infallible> {
+        let style = self.format.to_style(style);
+        let mut parts = content.split('\n');
+        if let Some(first) = parts.next() {
+            push_span(&mut self.current, first, style);
+        }
+        for part in parts {
+            self.lines.push(Line::from(mem::take(&mut self.current)));
+            push_span(&mut self.current, part, style);
+        }
+        Ok(())
+    }
+}
+
+const fn decorations(style: MessageStyle) -> [(Modifier, Option<bool>); 4] {
+    [
+        (Modifier::BOLD, style.bold),
+        (Modifier::ITALIC, style.italic),
+        (Modifier::UNDERLINED, style.underline),
+        (Modifier::CROSSED_OUT, style.strikethrough),
+    ]
+}
+
+fn push_span(spans: &mut Vec<Span<'static>>, content: &str, style: Style) {
+    let content = content.strip_suffix('\r').unwrap_or(content);
+    if !content.is_empty() {
+        spans.push(Span::styled(content.to_owned(), style));
+    }
+}
+
+const fn from_color(color: Color) -> Option<Color32> {
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb(r, g, b) => return Some(Color32::from_rgb(r, g, b)),
+        Color::Indexed(index) => index,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+    };
+    Some(ansi::from_indexed(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{
+        style::{Color, Modifier, Style},
+        text::{Line, Span},
+    };
+
+    use crate::{Color32, IntoMessage, Message, MessageStyle, Styleable};
+
+    use super::StyleToRatatui;
+    use crate::ansi::ColorMode;
+
+    #[test]
+    fn lines() {
+        let msg = "a\nb".bold().with("c\r\n".with("\nd".italic()));
+        let lines = StyleToRatatui::default().to_lines(&msg);
+        assert_eq!(
+            vec![
+                Line::from(Span::styled("a", Style::new().add_modifier(Modifier::BOLD))),
+                Line::from(vec![
+                    Span::styled("b", Style::new().add_modifier(Modifier::BOLD)),
+                    Span::styled("c", Style::new().add_modifier(Modifier::BOLD)),
+                ]),
+                Line::default(),
+                Line::from(Span::styled(
+                    "d",
+                    Style::new().add_modifier(Modifier::BOLD | Modifier::ITALIC)
+                )),
+            ],
+            lines,
+        );
+    }
+
+    #[test]
+    fn style() {
+        let style_to_ratatui = StyleToRatatui {
+            colors: ColorMode::Indexed,
+        };
+        let style = MessageStyle::new()
+            .color(Color32::RED)
+            .with_bold(Some(false))
+            .underline();
+
+        let converted = style_to_ratatui.to_style(style);
+        assert_eq!(
+            Style::new()
+                .fg(Color::Indexed(196))
+                .remove_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::UNDERLINED),
+            converted,
+        );
+        assert_eq!(style, style_to_ratatui.from_style(converted));
+    }
+
+    #[test]
+    fn round_trip() {
+        let style_to_ratatui = StyleToRatatui::default();
+        let msg = Message::default()
+            .with("one ".color(Color32::RED))
+            .with("two\nthree".bold())
+            .with("\nfour".italic());
+
+        let text = style_to_ratatui.to_text(&msg);
+        assert_eq!(3, text.lines.len());
+        assert_eq!(
+            msg.to_string(),
+            style_to_ratatui.from_text(&text).to_string()
+        );
+        assert_eq!(
+            Message::default()
+                .with("one ".color(Color32::RED))
+                .with("two".bold()),
+            style_to_ratatui.from_line(&text.lines[0]),
+        );
+    }
+}
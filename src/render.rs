@@ -0,0 +1,141 @@
+//! A common interface for outputs which [`Message`]s can be rendered to.
+//!
+//! Each output format implements [`Renderer`], which receives the flattened, styled segments of
+//! a message between calls to [`Renderer::begin`] and [`Renderer::end`]. Errors from the output,
+//! such as I/O errors, are propagated to the caller of [`Message::render`] rather than being
+//! discarded.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::convert::Infallible;
+//! use expedition::{render::Renderer, IntoMessage, MessageStyle, Styleable};
+//!
+//! /// Renders bold text in upper case.
+//! #[derive(Default)]
+//! struct Shouty(String);
+//!
+//! impl Renderer for Shouty {
+//!     type Error = Infallible;
+//!
+//!     fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Infallible> {
+//!         match style.bold {
+//!             Some(true) => self.0.push_str(&content.to_uppercase()),
+//!             _ => self.0.push_str(content),
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut renderer = Shouty::default();
+//! "Do ".with("not".bold()).with(" panic").render(&mut renderer).unwrap();
+//! assert_eq!("Do NOT panic", renderer.0);
+//! ```
+
+use std::ops::ControlFlow;
+
+use crate::{Message, MessageStyle, TryStackFlattener};
+
+/// An output which the styled segments of a [`Message`] can be rendered to.
+///
+/// See [`Message::render`].
+pub trait Renderer {
+    /// Error returned when rendering fails.
+    ///
+    /// Renderers which cannot fail use [`std::convert::Infallible`].
+    type Error;
+
+    /// Called before the first segment of a message.
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Renders a segment of text with the fully merged style of the node it is in.
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Self::Error>;
+
+    /// Called after the last segment of a message, even if rendering a segment failed.
+    fn end(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<R: Renderer + ?Sized> Renderer for &mut R {
+    type Error = R::Error;
+
+    fn begin(&mut self) -> Result<(), Self::Error> {
+        (**self).begin()
+    }
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> Result<(), Self::Error> {
+        (**self).segment(content, style)
+    }
+
+    fn end(&mut self) -> Result<(), Self::Error> {
+        (**self).end()
+    }
+}
+
+impl Message {
+    /// Renders this message to `renderer`.
+    ///
+    /// This uses [`Message::try_flatten`] to convert from a node hierarchy to a linear sequence
+    /// of segments. Once a segment fails to render, the traversal stops, but [`Renderer::end`]
+    /// is still called so that the output can be cleaned up.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from rendering a segment, or otherwise the error from
+    /// [`Renderer::end`].
+    pub fn render<R: Renderer + ?Sized>(&self, renderer: &mut R) -> Result<(), R::Error> {
+        renderer.begin()?;
+        let mut flattener =
+            TryStackFlattener::new(|content, style| match renderer.segment(content, style) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(err) => ControlFlow::Break(err),
+            });
+        let result = match self.try_flatten(&mut flattener) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(err) => Err(err),
+        };
+        let end = renderer.end();
+        result.and(end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::{IntoMessage, MessageStyle};
+
+    #[derive(Default)]
+    struct Failing {
+        segments: Vec<String>,
+        ended: bool,
+    }
+
+    impl Renderer for Failing {
+        type Error = String;
+
+        fn segment(&mut self, content: &str, _: MessageStyle) -> Result<(), String> {
+            if content == "fail" {
+                return Err(content.to_owned());
+            }
+            self.segments.push(content.to_owned());
+            Ok(())
+        }
+
+        fn end(&mut self) -> Result<(), String> {
+            self.ended = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stops_on_error() {
+        let mut renderer = Failing::default();
+        let msg = "a".with("fail").with("b");
+        assert_eq!(Err("fail".to_owned()), msg.render(&mut renderer));
+        assert_eq!(vec!["a"], renderer.segments);
+        assert!(renderer.ended);
+    }
+}
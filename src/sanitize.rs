@@ -0,0 +1,669 @@
+//! Validation and sanitization of [`Message`]s received from untrusted sources.
+//!
+//! Messages sent by users, such as chat messages from players, may be maliciously crafted to be
+//! extremely deep or large, or to contain characters which mess with how the surrounding text is
+//! displayed. A [`Sanitizer`] defines limits on what a message may contain, and can be used to:
+//! - check if a message fits within the limits, using [`Sanitizer::validate`]
+//! - modify a message so that it fits within the limits, using [`Sanitizer::sanitize`]
+//! - enforce the limits while deserializing a message, using `Sanitizer::deserialize` with the
+//!   `serde` feature
+//!
+//! All of these operations traverse the message without recursion, so they are safe to use on
+//! messages of any depth.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{sanitize::{AllowedStyles, Sanitizer}, Color32, IntoMessage, Styleable};
+//!
+//! let sanitizer = Sanitizer {
+//!     allowed_styles: AllowedStyles {
+//!         color: false,
+//!         ..Default::default()
+//!     },
+//!     ..Default::default()
+//! };
+//!
+//! let mut msg = "Hello \u{202e}world".color(Color32::RED).bold();
+//! assert!(sanitizer.validate(&msg).is_err());
+//!
+//! sanitizer.sanitize(&mut msg);
+//! assert_eq!("Hello world".bold(), msg);
+//! assert!(sanitizer.validate(&msg).is_ok());
+//! ```
+
+use std::{error::Error, fmt, mem, vec};
+
+use crate::{Message, MessageStyle};
+
+/// Limits and rules which a [`Message`] from an untrusted source must follow.
+///
+/// See the [module-level documentation](crate::sanitize) for usage info.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sanitizer {
+    /// Maximum number of nested message nodes, including the root node.
+    pub max_depth: usize,
+    /// Maximum number of message nodes in total, including the root node.
+    pub max_nodes: usize,
+    /// Maximum length of the content of all message nodes combined, in bytes.
+    pub max_content_len: usize,
+    /// Which fields of a [`MessageStyle`] may be set.
+    pub allowed_styles: AllowedStyles,
+    /// Whether to remove control characters, apart from `\n` and `\t`, from the content.
+    pub strip_control: bool,
+    /// Whether to remove Unicode bidirectional formatting characters from the content.
+    ///
+    /// These characters can be used to make text appear in a different order to how it is
+    /// actually stored, and can affect the text displayed after the message.
+    pub strip_bidi: bool,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_nodes: 256,
+            max_content_len: 4096,
+            allowed_styles: AllowedStyles::default(),
+            strip_control: true,
+            strip_bidi: true,
+        }
+    }
+}
+
+/// Which fields of a [`MessageStyle`] a [`Sanitizer`] allows to be set.
+///
+/// By default, all fields are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllowedStyles {
+    /// [`MessageStyle::color`]
+    pub color: bool,
+    /// [`MessageStyle::bold`]
+    pub bold: bool,
+    /// [`MessageStyle::italic`]
+    pub italic: bool,
+    /// [`MessageStyle::underline`]
+    pub underline: bool,
+    /// [`MessageStyle::strikethrough`]
+    pub strikethrough: bool,
+    /// [`MessageStyle::size`]
+    pub size: bool,
+    /// [`MessageStyle::family`]
+    pub family: bool,
+}
+
+impl Default for AllowedStyles {
+    fn default() -> Self {
+        Self {
+            color: true,
+            bold: true,
+            italic: true,
+            underline: true,
+            strikethrough: true,
+            size: true,
+            family: true,
+        }
+    }
+}
+
+impl AllowedStyles {
+    /// Creates a new style with only the allowed fields of `style` set.
+    #[must_use]
+    pub fn filter(&self, style: MessageStyle) -> MessageStyle {
+        fn allow<T>(allowed: bool, value: Option<T>) -> Option<T> {
+            value.filter(|_| allowed)
+        }
+
+        MessageStyle {
+            color: allow(self.color, style.color),
+            bold: allow(self.bold, style.bold),
+            italic: allow(self.italic, style.italic),
+            underline: allow(self.underline, style.underline),
+            strikethrough: allow(self.strikethrough, style.strikethrough),
+            size: allow(self.size, style.size),
+            family: allow(self.family, style.family),
+        }
+    }
+}
+
+/// A reason why a [`Message`] does not follow the rules of a [`Sanitizer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanitizeError {
+    /// The message nodes were nested deeper than [`Sanitizer::max_depth`].
+    TooDeep {
+        /// Maximum depth allowed.
+        max: usize,
+    },
+    /// There were more message nodes than [`Sanitizer::max_nodes`].
+    TooManyNodes {
+        /// Maximum number of nodes allowed.
+        max: usize,
+    },
+    /// The combined content was longer than [`Sanitizer::max_content_len`].
+    ContentTooLong {
+        /// Maximum content length allowed.
+        max: usize,
+    },
+    /// A node's style had a field set which is not allowed by [`Sanitizer::allowed_styles`].
+    DisallowedStyle(MessageStyle),
+    /// A node's content contained a character which should be stripped.
+    ForbiddenChar(char),
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooDeep { max } => write!(f, "message is nested deeper than {} nodes", max),
+            Self::TooManyNodes { max } => write!(f, "message has more than {} nodes", max),
+            Self::ContentTooLong { max } => {
+                write!(f, "message content is longer than {} bytes", max)
+            }
+            Self::DisallowedStyle(style) => write!(f, "style {:?} is not allowed", style),
+            Self::ForbiddenChar(c) => write!(f, "character {:?} is not allowed", c),
+        }
+    }
+}
+
+impl Error for SanitizeError {}
+
+impl Sanitizer {
+    /// Checks if a message follows all of the rules of this sanitizer.
+    ///
+    /// If this returns [`Ok`], then [`Sanitizer::sanitize`] will not make any changes to the
+    /// message.
+    pub fn validate(&self, msg: &Message) -> Result<(), SanitizeError> {
+        let mut nodes = 0;
+        let mut content_len = 0;
+        let mut stack = vec![(msg, 1)];
+        while let Some((node, depth)) = stack.pop() {
+            if depth > self.max_depth {
+                return Err(SanitizeError::TooDeep {
+                    max: self.max_depth,
+                });
+            }
+
+            nodes += 1;
+            if nodes > self.max_nodes {
+                return Err(SanitizeError::TooManyNodes {
+                    max: self.max_nodes,
+                });
+            }
+
+            content_len += node.content.len();
+            if content_len > self.max_content_len {
+                return Err(SanitizeError::ContentTooLong {
+                    max: self.max_content_len,
+                });
+            }
+
+            if self.allowed_styles.filter(node.style) != node.style {
+                return Err(SanitizeError::DisallowedStyle(node.style));
+            }
+
+            if let Some(c) = node.content.chars().find(|c| self.strips(*c)) {
+                return Err(SanitizeError::ForbiddenChar(c));
+            }
+
+            stack.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+        }
+        Ok(())
+    }
+
+    /// Modifies a message so that it follows all of the rules of this sanitizer.
+    ///
+    /// - disallowed style fields are unset
+    /// - control and bidirectional characters are removed, if enabled
+    /// - nodes nested deeper than the maximum depth are removed
+    /// - once the maximum number of nodes or content length is reached, all nodes after that
+    ///   point are removed, and the content of the node which reached the limit is truncated
+    ///
+    /// The root node is always kept, even if it is emptied.
+    pub fn sanitize(&self, msg: &mut Message) {
+        let mut used = Usage::default();
+        let mut stack = vec![self.enter(&mut used, mem::take(msg), 1)];
+        loop {
+            let top = stack.last_mut().expect("stack should never be empty here");
+            match top.children.next() {
+                Some(child) => {
+                    let full =
+                        used.nodes >= self.max_nodes || used.content_len >= self.max_content_len;
+                    if full || top.depth >= self.max_depth {
+                        drop(child);
+                    } else {
+                        let depth = top.depth + 1;
+                        stack.push(self.enter(&mut used, child, depth));
+                    }
+                }
+                None => {
+                    let mut frame = stack.pop().expect("stack should never be empty here");
+                    frame.node.children = frame.kept;
+                    match stack.last_mut() {
+                        Some(parent) => parent.kept.push(frame.node),
+                        None => {
+                            *msg = frame.node;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn enter(&self, used: &mut Usage, mut node: Message, depth: usize) -> Frame {
+        used.nodes += 1;
+        self.sanitize_node(&mut node);
+
+        let remaining = self.max_content_len.saturating_sub(used.content_len);
+        if node.content.len() > remaining {
+            let mut end = remaining;
+            while !node.content.is_char_boundary(end) {
+                end -= 1;
+            }
+            node.content.truncate(end);
+        }
+        used.content_len += node.content.len();
+
+        let children = mem::take(&mut node.children);
+        Frame {
+            kept: Vec::with_capacity(children.len()),
+            children: children.into_iter(),
+            node,
+            depth,
+        }
+    }
+
+    fn sanitize_node(&self, node: &mut Message) {
+        node.style = self.allowed_styles.filter(node.style);
+        if node.content.contains(|c| self.strips(c)) {
+            node.content.retain(|c| !self.strips(c));
+        }
+    }
+
+    fn strips(&self, c: char) -> bool {
+        (self.strip_control && is_forbidden_control(c)) || (self.strip_bidi && is_bidi(c))
+    }
+}
+
+#[derive(Default)]
+struct Usage {
+    nodes: usize,
+    content_len: usize,
+}
+
+struct Frame {
+    node: Message,
+    children: vec::IntoIter<Message>,
+    kept: Vec<Message>,
+    depth: usize,
+}
+
+fn is_forbidden_control(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+const fn is_bidi(c: char) -> bool {
+    matches!(
+        c,
+        '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// Drops messages without recursing into their children, which would otherwise overflow the
+/// stack for extremely deep messages.
+pub(crate) fn drop_iteratively(mut stack: Vec<Message>) {
+    while let Some(mut node) = stack.pop() {
+        stack.append(&mut node.children);
+    }
+}
+
+#[cfg(feature = "serde")]
+mod de {
+    use std::{cell::Cell, fmt};
+
+    use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+
+    use crate::{Message, MessageStyle};
+
+    use super::{SanitizeError, Sanitizer};
+
+    impl Sanitizer {
+        /// Deserializes a message, enforcing the limits of this sanitizer during parsing.
+        ///
+        /// The depth, node count and content length limits are checked while the message is
+        /// being deserialized, so that a malicious payload is rejected before it is fully read
+        /// into memory. If any of these limits are exceeded, a [`SanitizeError`] is returned as a
+        /// custom deserializer error.
+        ///
+        /// After deserializing, the rest of the rules are applied using [`Sanitizer::sanitize`].
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use expedition::{sanitize::Sanitizer, Message};
+        ///
+        /// let sanitizer = Sanitizer {
+        ///     max_depth: 2,
+        ///     ..Default::default()
+        /// };
+        ///
+        /// let json = r#"{"content":"a","style":{},"children":[
+        ///     {"content":"b","style":{},"children":[
+        ///         {"content":"c","style":{},"children":[]}
+        ///     ]}
+        /// ]}"#;
+        ///
+        /// let mut de = serde_json::Deserializer::from_str(json);
+        /// assert!(sanitizer.deserialize(&mut de).is_err());
+        /// ```
+        pub fn deserialize<'de, D>(&self, deserializer: D) -> Result<Message, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            let state = State {
+                sanitizer: self,
+                nodes: Cell::new(0),
+                content_len: Cell::new(0),
+            };
+            let mut msg = MessageSeed {
+                state: &state,
+                depth: 1,
+            }
+            .deserialize(deserializer)?;
+            self.sanitize(&mut msg);
+            Ok(msg)
+        }
+    }
+
+    struct State<'a> {
+        sanitizer: &'a Sanitizer,
+        nodes: Cell<usize>,
+        content_len: Cell<usize>,
+    }
+
+    impl State<'_> {
+        fn node<E: de::Error>(&self, depth: usize) -> Result<(), E> {
+            let sanitizer = self.sanitizer;
+            if depth > sanitizer.max_depth {
+                return Err(E::custom(SanitizeError::TooDeep {
+                    max: sanitizer.max_depth,
+                }));
+            }
+
+            self.nodes.set(self.nodes.get() + 1);
+            if self.nodes.get() > sanitizer.max_nodes {
+                return Err(E::custom(SanitizeError::TooManyNodes {
+                    max: sanitizer.max_nodes,
+                }));
+            }
+            Ok(())
+        }
+
+        fn content<E: de::Error>(&self, content: &str) -> Result<(), E> {
+            self.content_len.set(self.content_len.get() + content.len());
+            if self.content_len.get() > self.sanitizer.max_content_len {
+                return Err(E::custom(SanitizeError::ContentTooLong {
+                    max: self.sanitizer.max_content_len,
+                }));
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field {
+        Content,
+        Style,
+        Children,
+        #[serde(other)]
+        Other,
+    }
+
+    const FIELDS: &[&str] = &["content", "style", "children"];
+
+    #[derive(Clone, Copy)]
+    struct MessageSeed<'a> {
+        state: &'a State<'a>,
+        depth: usize,
+    }
+
+    impl<'de> DeserializeSeed<'de> for MessageSeed<'_> {
+        type Value = Message;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            self.state.node(self.depth)?;
+            deserializer.deserialize_struct("Message", FIELDS, self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for MessageSeed<'_> {
+        type Value = Message;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "struct Message")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let content: String = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            self.state.content(&content)?;
+            let style: MessageStyle = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let children = seq
+                .next_element_seed(self.children())?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            Ok(Message {
+                content,
+                style,
+                children,
+            })
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            let mut content = None;
+            let mut style = None;
+            let mut children = None;
+            while let Some(field) = map.next_key()? {
+                match field {
+                    Field::Content => {
+                        if content.is_some() {
+                            return Err(de::Error::duplicate_field("content"));
+                        }
+                        let value: String = map.next_value()?;
+                        self.state.content(&value)?;
+                        content = Some(value);
+                    }
+                    Field::Style => {
+                        if style.is_some() {
+                            return Err(de::Error::duplicate_field("style"));
+                        }
+                        style = Some(map.next_value()?);
+                    }
+                    Field::Children => {
+                        if children.is_some() {
+                            return Err(de::Error::duplicate_field("children"));
+                        }
+                        children = Some(map.next_value_seed(self.children())?);
+                    }
+                    Field::Other => {
+                        map.next_value::<de::IgnoredAny>()?;
+                    }
+                }
+            }
+
+            Ok(Message {
+                content: content.ok_or_else(|| de::Error::missing_field("content"))?,
+                style: style.ok_or_else(|| de::Error::missing_field("style"))?,
+                children: children.ok_or_else(|| de::Error::missing_field("children"))?,
+            })
+        }
+    }
+
+    impl<'a> MessageSeed<'a> {
+        const fn children(self) -> ChildrenSeed<'a> {
+            ChildrenSeed {
+                child: MessageSeed {
+                    state: self.state,
+                    depth: self.depth + 1,
+                },
+            }
+        }
+    }
+
+    struct ChildrenSeed<'a> {
+        child: MessageSeed<'a>,
+    }
+
+    impl<'de> DeserializeSeed<'de> for ChildrenSeed<'_> {
+        type Value = Vec<Message>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: de::Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for ChildrenSeed<'_> {
+        type Value = Vec<Message>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a sequence of Message")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut children = Vec::new();
+            while let Some(child) = seq.next_element_seed(self.child)? {
+                children.push(child);
+            }
+            Ok(children)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color32, IntoMessage, Message, Styleable};
+
+    use super::{SanitizeError, Sanitizer};
+
+    fn deep(depth: usize) -> Message {
+        let mut msg = Message::new("leaf");
+        for _ in 1..depth {
+            msg = Message::default().with(msg);
+        }
+        msg
+    }
+
+    #[test]
+    fn depth() {
+        let sanitizer = Sanitizer {
+            max_depth: 2,
+            ..Default::default()
+        };
+
+        let mut msg = "a".with("b".with("c")).with("d");
+        assert_eq!(
+            Err(SanitizeError::TooDeep { max: 2 }),
+            sanitizer.validate(&msg)
+        );
+        sanitizer.sanitize(&mut msg);
+        assert_eq!("a".with("b").with("d"), msg);
+        assert_eq!(Ok(()), sanitizer.validate(&msg));
+    }
+
+    #[test]
+    fn extremely_deep() {
+        let sanitizer = Sanitizer::default();
+        let mut msg = deep(1_000_000);
+        assert_eq!(
+            Err(SanitizeError::TooDeep { max: 32 }),
+            sanitizer.validate(&msg)
+        );
+        sanitizer.sanitize(&mut msg);
+        assert_eq!(Ok(()), sanitizer.validate(&msg));
+    }
+
+    #[test]
+    fn nodes() {
+        let sanitizer = Sanitizer {
+            max_nodes: 3,
+            ..Default::default()
+        };
+
+        let mut msg = "a".with("b".with("c")).with("d");
+        assert_eq!(
+            Err(SanitizeError::TooManyNodes { max: 3 }),
+            sanitizer.validate(&msg)
+        );
+        sanitizer.sanitize(&mut msg);
+        assert_eq!("a".with("b".with("c")), msg);
+    }
+
+    #[test]
+    fn content_len() {
+        let sanitizer = Sanitizer {
+            max_content_len: 6,
+            ..Default::default()
+        };
+
+        let mut msg = "ab".with("cd".with("éf")).with("g");
+        assert_eq!(
+            Err(SanitizeError::ContentTooLong { max: 6 }),
+            sanitizer.validate(&msg)
+        );
+        sanitizer.sanitize(&mut msg);
+        assert_eq!("ab".with("cd".with("é")), msg);
+    }
+
+    #[test]
+    fn chars() {
+        let sanitizer = Sanitizer::default();
+        let mut msg = "a\u{7}b\n".with("\u{2066}c\td".color(Color32::RED));
+        assert_eq!(
+            Err(SanitizeError::ForbiddenChar('\u{7}')),
+            sanitizer.validate(&msg)
+        );
+        sanitizer.sanitize(&mut msg);
+        assert_eq!("ab\n".with("c\td".color(Color32::RED)), msg);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        let sanitizer = Sanitizer {
+            max_nodes: 2,
+            ..Default::default()
+        };
+
+        let msg = "a\u{202e}".with("b".bold());
+        let json = serde_json::to_string(&msg).unwrap();
+        let mut de = serde_json::Deserializer::from_str(&json);
+        assert_eq!(
+            "a".with("b".bold()),
+            sanitizer.deserialize(&mut de).unwrap()
+        );
+
+        let msg = "a".with("b").with("c");
+        let json = serde_json::to_string(&msg).unwrap();
+        let mut de = serde_json::Deserializer::from_str(&json);
+        assert!(sanitizer.deserialize(&mut de).is_err());
+    }
+}
@@ -0,0 +1,498 @@
+//! Searching for and replacing text in [`Message`]s, across node boundaries.
+//!
+//! Text in a message may be split across several nodes with different styles, so searching
+//! each node's content individually would miss matches that span multiple nodes. Instead, the
+//! functions here search the plain text of the whole message (as given by its [`fmt::Display`]
+//! implementation), then map the matched ranges back onto the nodes that they cover.
+//!
+//! When a match is replaced or restyled, only the matched text is changed - the text around the
+//! match, and the node hierarchy around it, keeps its original styling. When a message is split
+//! at its matches with [`Message::split`], each piece keeps the styling of its text, and pieces
+//! can be put back together with [`Message::join`].
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{Color32, IntoMessage, Message, MessageStyle, Styleable};
+//!
+//! // "Alice" is split over two nodes with different styles
+//! let mut msg = "Hello Al".with("ice!".bold());
+//! assert_eq!(vec![6..11], msg.find("Alice"));
+//!
+//! // mention highlighting
+//! msg.restyle("Alice", MessageStyle::new().color(Color32::GOLD));
+//! assert_eq!(
+//!     "Hello "
+//!         .with("Al".color(Color32::GOLD))
+//!         // `ice` is restyled inside of the bold node, so it stays bold
+//!         .with(Message::default().bold()
+//!             .with("ice".color(Color32::GOLD))
+//!             .with("!")),
+//!     msg,
+//! );
+//!
+//! // profanity filter
+//! let mut msg = "Oh da".with("rn it".italic());
+//! msg.replace("darn", "****");
+//! assert_eq!("Oh **** it", msg.to_string());
+//! ```
+//!
+//! [`fmt::Display`]: std::fmt::Display
+
+use std::ops::Range;
+
+use crate::{Message, MessageStyle, MessageVisitorMut, Styleable};
+
+/// A pattern which can be searched for in the plain text of a [`Message`].
+///
+/// This is implemented for string slices and characters, which match literally, and for
+/// `regex::Regex` if the `regex` feature is enabled.
+pub trait MessagePattern {
+    /// Finds the byte ranges of all non-overlapping matches of this pattern in `haystack`, in
+    /// order.
+    ///
+    /// Empty matches are ignored.
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>>;
+}
+
+impl MessagePattern for str {
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        haystack
+            .match_indices(self)
+            .map(|(start, matched)| start..start + matched.len())
+            .collect()
+    }
+}
+
+impl MessagePattern for String {
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>> {
+        self.as_str().find_in(haystack)
+    }
+}
+
+impl MessagePattern for char {
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>> {
+        haystack
+            .match_indices(*self)
+            .map(|(start, matched)| start..start + matched.len())
+            .collect()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl MessagePattern for regex::Regex {
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>> {
+        self.find_iter(haystack)
+            .map(|m| m.range())
+            .filter(|range| !range.is_empty())
+            .collect()
+    }
+}
+
+impl<P: MessagePattern + ?Sized> MessagePattern for &P {
+    fn find_in(&self, haystack: &str) -> Vec<Range<usize>> {
+        (**self).find_in(haystack)
+    }
+}
+
+impl Message {
+    /// Finds the byte ranges of all matches of `pattern` in the plain text of this message.
+    ///
+    /// The ranges index into the output of [`ToString::to_string`] on this message.
+    pub fn find(&self, pattern: impl MessagePattern) -> Vec<Range<usize>> {
+        pattern.find_in(&self.to_string())
+    }
+
+    /// Replaces all matches of `pattern` with a copy of `replacement`.
+    ///
+    /// The replacement is inserted into the node where the match starts, so it inherits the
+    /// style of the matched text at that point, with its own style applied on top.
+    ///
+    /// Returns the number of matches replaced.
+    pub fn replace(&mut self, pattern: impl MessagePattern, replacement: impl Into<Self>) -> usize {
+        let replacement = replacement.into();
+        self.replace_with(pattern, |_| replacement.clone())
+    }
+
+    /// Replaces all matches of `pattern` with the message returned by `f`, which is given the
+    /// matched text.
+    ///
+    /// See [`Message::replace`] for how the replacement is styled.
+    ///
+    /// Returns the number of matches replaced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{Color32, IntoMessage, Styleable};
+    /// let mut msg = "Hello @alice and @bob".into_text();
+    /// msg.replace_with('@', |_| "@".color(Color32::GOLD));
+    /// assert_eq!(
+    ///     "Hello "
+    ///         .with("@".color(Color32::GOLD))
+    ///         .with("alice and ")
+    ///         .with("@".color(Color32::GOLD))
+    ///         .with("bob"),
+    ///     msg,
+    /// );
+    /// ```
+    pub fn replace_with<F>(&mut self, pattern: impl MessagePattern, mut f: F) -> usize
+    where
+        F: FnMut(&str) -> Self,
+    {
+        let text = self.to_string();
+        let matches = pattern.find_in(&text);
+        let replacements = matches
+            .iter()
+            .map(|range| f(&text[range.clone()]))
+            .collect();
+        self.edit_ranges(&matches, Edit::Replace(replacements));
+        matches.len()
+    }
+
+    /// Applies `style` on top of the existing style of all text matching `pattern`.
+    ///
+    /// Returns the number of matches restyled.
+    pub fn restyle(&mut self, pattern: impl MessagePattern, style: MessageStyle) -> usize {
+        let matches = self.find(pattern);
+        self.edit_ranges(&matches, Edit::Restyle(style));
+        matches.len()
+    }
+
+    /// Splits this message at each match of `pattern`, removing the matched text.
+    ///
+    /// Each piece keeps the final style of every character, but is flattened as in
+    /// [`Message::normalized`], even if there are no matches and the only piece is the whole
+    /// message. Like [`str::split`], matches at the start or end, or next to each other, produce
+    /// empty pieces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{Color32, IntoMessage, Message, Styleable};
+    /// let msg = "HP: ".with("100, ".color(Color32::GREEN)).with("MP: 50".bold());
+    /// assert_eq!(
+    ///     vec![
+    ///         Message::default().with("HP: ").with("100".color(Color32::GREEN)),
+    ///         "MP: 50".bold(),
+    ///     ],
+    ///     msg.split(", "),
+    /// );
+    /// ```
+    pub fn split(&self, pattern: impl MessagePattern) -> Vec<Self> {
+        let text = self.to_string();
+        let matches = pattern.find_in(&text);
+        if matches.is_empty() {
+            return vec![self.normalized()];
+        }
+
+        let mut pieces = Vec::with_capacity(matches.len() + 1);
+        let mut start = 0;
+        for range in matches {
+            pieces.push(start..range.start);
+            start = range.end;
+        }
+        pieces.push(start..text.len());
+        self.slices(&pieces)
+    }
+
+    /// Splits this message into lines, like [`str::lines`].
+    ///
+    /// Lines are split at `\n` or `\r\n`, which are removed, and there is no empty line after a
+    /// final line break. See [`Message::split`] for how the lines are styled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Message, Styleable};
+    /// let msg = "first".with("\r\nsecond\n".italic());
+    /// assert_eq!(
+    ///     vec![Message::new("first"), "second".italic()],
+    ///     msg.lines(),
+    /// );
+    /// ```
+    pub fn lines(&self) -> Vec<Self> {
+        let text = self.to_string();
+        let breaks = '\n'.find_in(&text);
+        if breaks.is_empty() {
+            return if text.is_empty() {
+                Vec::new()
+            } else {
+                vec![self.normalized()]
+            };
+        }
+
+        let mut lines = Vec::with_capacity(breaks.len() + 1);
+        let mut start = 0;
+        for range in breaks {
+            let end = if text[..range.start].ends_with('\r') {
+                range.start - 1
+            } else {
+                range.start
+            };
+            lines.push(start..end);
+            start = range.end;
+        }
+        if start < text.len() {
+            lines.push(start..text.len());
+        }
+        self.slices(&lines)
+    }
+
+    /// Joins `messages` into a single message, with a copy of `separator` between each one.
+    ///
+    /// Each message and separator becomes a child of an unstyled root node with no content, so
+    /// their hierarchies are kept as they are, without flattening them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{Color32, IntoMessage, Message, Styleable};
+    /// let players = ["alice".bold(), "bob".italic()];
+    /// let msg = Message::join(players, ", ".color(Color32::GRAY));
+    /// assert_eq!("alice, bob", msg.to_string());
+    /// ```
+    pub fn join<M: Into<Self>>(
+        messages: impl IntoIterator<Item = M>,
+        separator: impl Into<Self>,
+    ) -> Self {
+        let separator = separator.into();
+        let mut children = Vec::new();
+        for (i, message) in messages.into_iter().enumerate() {
+            if i > 0 {
+                children.push(separator.clone());
+            }
+            children.push(message.into());
+        }
+        Self {
+            content: String::new(),
+            style: MessageStyle::default(),
+            children,
+        }
+    }
+
+    /// Creates a message from each of the byte `ranges` of the plain text of this message,
+    /// which must be in order and not overlap, using a single traversal.
+    fn slices(&self, ranges: &[Range<usize>]) -> Vec<Self> {
+        let mut pieces = vec![Vec::new(); ranges.len()];
+        // first range which may still overlap the next segment
+        let mut first = 0;
+        let mut offset = 0;
+        for (content, style) in self.segments() {
+            let segment = offset..offset + content.len();
+            offset = segment.end;
+
+            for (i, range) in ranges.iter().enumerate().skip(first) {
+                if range.start >= segment.end {
+                    break;
+                }
+                let start = range.start.max(segment.start);
+                let end = range.end.min(segment.end);
+                if start < end {
+                    pieces[i].push((&content[start - segment.start..end - segment.start], style));
+                }
+                if range.end <= segment.end {
+                    first = i + 1;
+                }
+            }
+        }
+        pieces.into_iter().map(Self::from_segments).collect()
+    }
+
+    fn edit_ranges(&mut self, matches: &[Range<usize>], edit: Edit) {
+        if matches.is_empty() {
+            return;
+        }
+
+        self.visit_mut(&mut Editor {
+            matches,
+            edit,
+            offset: 0,
+            starts: Vec::new(),
+        });
+    }
+}
+
+enum Edit {
+    /// Replaces the match at each index with the message at the same index.
+    Replace(Vec<Message>),
+    Restyle(MessageStyle),
+}
+
+/// Edits the content of each node once it has been exited, so that the new nodes inserted as
+/// children are not visited themselves.
+struct Editor<'a> {
+    matches: &'a [Range<usize>],
+    edit: Edit,
+    /// Offset of the next node's content in the plain text.
+    offset: usize,
+    /// Offsets of the content of the nodes currently entered.
+    starts: Vec<usize>,
+}
+
+impl MessageVisitorMut for Editor<'_> {
+    fn enter(&mut self, node: &mut Message, _: MessageStyle) {
+        self.starts.push(self.offset);
+        self.offset += node.content.len();
+    }
+
+    fn exit(&mut self, node: &mut Message, _: MessageStyle) {
+        let start = self.starts.pop().expect("exited more nodes than entered");
+        let end = start + node.content.len();
+
+        let first = self.matches.partition_point(|m| m.end <= start);
+        let overlapping = self.matches[first..]
+            .iter()
+            .enumerate()
+            .take_while(|(_, m)| m.start < end);
+
+        let mut content = None;
+        let mut inserted = Vec::new();
+        let mut keep = |text: &str, inserted: &mut Vec<Message>| {
+            if content.is_none() && inserted.is_empty() {
+                content = Some(text.to_owned());
+            } else {
+                inserted.push(Message::new(text));
+            }
+        };
+
+        let mut cursor = start;
+        for (i, range) in overlapping {
+            let match_start = range.start.max(start);
+            let match_end = range.end.min(end);
+            if cursor < match_start {
+                keep(
+                    &node.content[cursor - start..match_start - start],
+                    &mut inserted,
+                );
+            }
+
+            match &self.edit {
+                Edit::Replace(replacements) => {
+                    if range.start >= start {
+                        inserted.push(replacements[first + i].clone());
+                    }
+                }
+                Edit::Restyle(style) => {
+                    let text = &node.content[match_start - start..match_end - start];
+                    inserted.push(Message::new(text).with_style(*style));
+                }
+            }
+            cursor = match_end;
+        }
+
+        if cursor == start {
+            // no matches in this node's content
+            return;
+        }
+        if cursor < end {
+            keep(&node.content[cursor - start..], &mut inserted);
+        }
+
+        node.content = content.unwrap_or_default();
+        node.children.splice(0..0, inserted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Color32, IntoMessage, Message, MessageStyle, Styleable};
+
+    #[test]
+    fn find_across_nodes() {
+        let msg = "ab".with("c".bold().with("d")).with("e");
+        assert_eq!(vec![1..4], msg.find("bcd"));
+        assert_eq!(vec![3..4], msg.find('d'));
+        assert!(msg.find("").is_empty());
+    }
+
+    #[test]
+    fn replace_keeps_surrounding_style() {
+        let mut msg = "one two".color(Color32::RED).with(" three".bold());
+        assert_eq!(1, msg.replace("two th", "2 3".italic()));
+        assert_eq!(
+            "one "
+                .color(Color32::RED)
+                .with("2 3".italic())
+                .with("ree".bold()),
+            msg,
+        );
+    }
+
+    #[test]
+    fn restyle_within_node() {
+        let mut msg = "aXbXc".into_text();
+        msg.restyle('X', MessageStyle::new().bold());
+        assert_eq!(
+            "a".with("X".bold()).with("b").with("X".bold()).with("c"),
+            msg,
+        );
+    }
+
+    #[test]
+    fn restyle_whole_node() {
+        let mut msg = Message::default().with("abc".italic()).with("d");
+        msg.restyle("abc", MessageStyle::new().bold());
+        assert_eq!(
+            Message::default()
+                .with(Message::default().italic().with("abc".bold()))
+                .with("d"),
+            msg,
+        );
+    }
+
+    #[test]
+    fn split_across_nodes() {
+        let msg = "a,".with("b,".bold().with(",c")).with(",");
+        assert_eq!(
+            vec![
+                Message::new("a"),
+                "b".bold(),
+                Message::default(),
+                "c".bold(),
+                Message::default(),
+            ],
+            msg.split(','),
+        );
+        assert_eq!(vec![msg.normalized()], msg.split(';'));
+    }
+
+    #[test]
+    fn lines() {
+        let msg = "one\r".with("\n\ntwo".underline());
+        assert_eq!(
+            vec![Message::new("one"), Message::default(), "two".underline()],
+            msg.lines(),
+        );
+        assert!(Message::default().lines().is_empty());
+        assert_eq!(vec![Message::default()], Message::new("\n").lines());
+        let msg = "one ".bold().with("two".bold());
+        assert_eq!(vec!["one two".bold()], msg.lines());
+    }
+
+    #[test]
+    fn join() {
+        let msg = Message::join(["a".bold(), "b".into_text()], ", ".italic());
+        assert_eq!(
+            Message::default()
+                .with("a".bold())
+                .with(", ".italic())
+                .with("b"),
+            msg,
+        );
+        assert_eq!(vec!["a".bold(), Message::new("b")], msg.split(", "));
+        assert_eq!(Message::default(), Message::join([""; 0], ","));
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex() {
+        let mut msg = "call 555".with("-1234".bold()).with(" now");
+        let re = regex::Regex::new(r"\d{3}-\d{4}").unwrap();
+        assert_eq!(1, msg.replace(&re, "[redacted]"));
+        assert_eq!("call [redacted] now", msg.to_string());
+    }
+}
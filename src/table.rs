@@ -0,0 +1,437 @@
+//! Features for laying out [`Message`]s as a table in fixed-width outputs such as terminals.
+//!
+//! A [`Table`] is built from rows of message cells, and converted into a single message with
+//! [`Table::to_message`], which keeps the style of every cell and can be rendered by any output.
+//!
+//! # Examples
+//!
+//! ```
+//! use expedition::{
+//!     layout::Alignment,
+//!     table::{BorderChars, Table},
+//!     Color32, MessageStyle, Styleable,
+//! };
+//!
+//! let table = Table::new()
+//!     .header(["Player".bold(), "Score".bold()])
+//!     .row(["alice".into(), "1200".color(Color32::GREEN)])
+//!     .row(["bob".into(), "85".color(Color32::RED)])
+//!     .align(1, Alignment::Right)
+//!     .border(BorderChars::ASCII)
+//!     .border_style(MessageStyle::new().color(Color32::GRAY));
+//!
+//! assert_eq!(
+//!     "\
+//! +--------+-------+
+//! | Player | Score |
+//! +--------+-------+
+//! | alice  |  1200 |
+//! | bob    |    85 |
+//! +--------+-------+",
+//!     table.to_message().to_string(),
+//! );
+//! ```
+
+use crate::{
+    layout::{self, Alignment, Line},
+    Message, MessageStyle,
+};
+
+/// Characters used to draw the borders of a [`Table`].
+///
+/// The names of the corner and junction characters describe where they are in the table, so
+/// for example [`BorderChars::top`] joins the top edge to a vertical line between two columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BorderChars {
+    /// Horizontal lines.
+    pub horizontal: char,
+    /// Vertical lines.
+    pub vertical: char,
+    /// Top left corner.
+    pub top_left: char,
+    /// Junction of the top edge and a vertical line.
+    pub top: char,
+    /// Top right corner.
+    pub top_right: char,
+    /// Junction of the left edge and the line below the header.
+    pub left: char,
+    /// Junction of the line below the header and a vertical line.
+    pub cross: char,
+    /// Junction of the right edge and the line below the header.
+    pub right: char,
+    /// Bottom left corner.
+    pub bottom_left: char,
+    /// Junction of the bottom edge and a vertical line.
+    pub bottom: char,
+    /// Bottom right corner.
+    pub bottom_right: char,
+}
+
+impl BorderChars {
+    /// Borders drawn with `-`, `|` and `+`, which can be shown by any output.
+    pub const ASCII: Self = Self {
+        horizontal: '-',
+        vertical: '|',
+        top_left: '+',
+        top: '+',
+        top_right: '+',
+        left: '+',
+        cross: '+',
+        right: '+',
+        bottom_left: '+',
+        bottom: '+',
+        bottom_right: '+',
+    };
+
+    /// Borders drawn with light box drawing characters.
+    pub const LIGHT: Self = Self {
+        horizontal: '─',
+        vertical: '│',
+        top_left: '┌',
+        top: '┬',
+        top_right: '┐',
+        left: '├',
+        cross: '┼',
+        right: '┤',
+        bottom_left: '└',
+        bottom: '┴',
+        bottom_right: '┘',
+    };
+}
+
+/// Layout settings of a single column of a [`Table`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Column {
+    /// How the content of each cell is aligned within the column.
+    pub align: Alignment,
+    /// Maximum display width of the content of each cell, or [`None`] for no limit. Cells which
+    /// are wider are wrapped onto multiple lines.
+    pub max_width: Option<usize>,
+}
+
+/// Builder for a table of [`Message`] cells.
+///
+/// The width of each column is the largest display width of the cells in it, measured using
+/// [`unicode_width`], so that wide characters such as CJK are aligned correctly. Cells may
+/// contain line breaks, and are also wrapped at word boundaries if they are wider than the
+/// [`Column::max_width`] of their column. Rows with fewer cells than others are filled with
+/// empty cells.
+///
+/// Each cell has [`Table::padding`] spaces on either side of its content. Without a border,
+/// there is no padding at the outer edges of the table, so columns are separated by twice
+/// the padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    header: Option<Vec<Message>>,
+    rows: Vec<Vec<Message>>,
+    columns: Vec<Column>,
+    padding: usize,
+    border: Option<BorderChars>,
+    border_style: MessageStyle,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Table {
+    /// Creates an empty table with no border, and a padding of 1.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            header: None,
+            rows: Vec::new(),
+            columns: Vec::new(),
+            padding: 1,
+            border: None,
+            border_style: MessageStyle::new(),
+        }
+    }
+
+    /// Sets the header row, which is separated from the other rows by a line if the table has a
+    /// border.
+    #[must_use]
+    pub fn header<M: Into<Message>>(mut self, cells: impl IntoIterator<Item = M>) -> Self {
+        self.header = Some(cells.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Adds a row below all existing rows.
+    #[must_use]
+    pub fn row<M: Into<Message>>(mut self, cells: impl IntoIterator<Item = M>) -> Self {
+        self.push_row(cells);
+        self
+    }
+
+    /// Adds a row below all existing rows.
+    pub fn push_row<M: Into<Message>>(&mut self, cells: impl IntoIterator<Item = M>) {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+    }
+
+    /// Sets the layout settings of the column at `index`.
+    #[must_use]
+    pub fn column(mut self, index: usize, column: Column) -> Self {
+        if self.columns.len() <= index {
+            self.columns.resize(index + 1, Column::default());
+        }
+        self.columns[index] = column;
+        self
+    }
+
+    /// Sets how the cells of the column at `index` are aligned.
+    #[must_use]
+    pub fn align(self, index: usize, align: Alignment) -> Self {
+        let column = self.column_at(index);
+        self.column(index, Column { align, ..column })
+    }
+
+    /// Sets the maximum display width of the cells of the column at `index`.
+    #[must_use]
+    pub fn max_width(self, index: usize, max_width: usize) -> Self {
+        let column = self.column_at(index);
+        self.column(
+            index,
+            Column {
+                max_width: Some(max_width),
+                ..column
+            },
+        )
+    }
+
+    /// Sets the number of spaces on either side of the content of each cell.
+    #[must_use]
+    pub const fn padding(mut self, padding: usize) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Draws a border around the table and between columns, using `chars`.
+    #[must_use]
+    pub const fn border(mut self, chars: BorderChars) -> Self {
+        self.border = Some(chars);
+        self
+    }
+
+    /// Sets the style of the border.
+    #[must_use]
+    pub const fn border_style(mut self, style: MessageStyle) -> Self {
+        self.border_style = style;
+        self
+    }
+
+    /// Gets the layout settings of the column at `index`.
+    #[must_use]
+    pub fn column_at(&self, index: usize) -> Column {
+        self.columns.get(index).copied().unwrap_or_default()
+    }
+
+    /// Lays out the table as a single message, with rows separated by line breaks.
+    ///
+    /// There is no line break after the last row.
+    #[must_use]
+    pub fn to_message(&self) -> Message {
+        let rows: Vec<(bool, &Vec<Message>)> = self
+            .header
+            .iter()
+            .map(|row| (true, row))
+            .chain(self.rows.iter().map(|row| (false, row)))
+            .collect();
+        let column_count = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+
+        // wrap every cell, then find the widest line in each column
+        let empty = Message::default();
+        let cells: Vec<Vec<Vec<Line>>> = rows
+            .iter()
+            .map(|(_, row)| {
+                (0..column_count)
+                    .map(|index| {
+                        let width = self.column_at(index).max_width.unwrap_or(usize::MAX);
+                        layout::wrap(row.get(index).unwrap_or(&empty), width)
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut widths = vec![0; column_count];
+        for row in &cells {
+            for (width, lines) in widths.iter_mut().zip(row) {
+                for line in lines {
+                    *width = (*width).max(layout::line_width(line));
+                }
+            }
+        }
+
+        let mut out = Line::new();
+        if let Some(chars) = self.border {
+            let corners = (chars.top_left, chars.top, chars.top_right);
+            self.push_rule(&mut out, &widths, corners);
+        }
+        for (row_index, row) in cells.iter().enumerate() {
+            let height = row.iter().map(Vec::len).max().unwrap_or(1);
+            for line_index in 0..height {
+                if !out.is_empty() {
+                    layout::push_run(&mut out, "\n", MessageStyle::new());
+                }
+                self.push_line(&mut out, &widths, row, line_index);
+            }
+
+            let is_header = rows[row_index].0;
+            if let (Some(chars), true) = (self.border, is_header && row_index + 1 < rows.len()) {
+                self.push_rule(&mut out, &widths, (chars.left, chars.cross, chars.right));
+            }
+        }
+        if let Some(chars) = self.border {
+            let corners = (chars.bottom_left, chars.bottom, chars.bottom_right);
+            self.push_rule(&mut out, &widths, corners);
+        }
+
+        layout::to_message(&out)
+    }
+
+    /// Appends one line of text of a row, with cells separated by borders or padding.
+    fn push_line(&self, out: &mut Line, widths: &[usize], row: &[Vec<Line>], line_index: usize) {
+        let unstyled = MessageStyle::new();
+        let vertical = self.border.map(|chars| chars.vertical.to_string());
+        if let Some(vertical) = &vertical {
+            layout::push_run(out, vertical, self.border_style);
+        }
+
+        for (index, (width, lines)) in widths.iter().zip(row).enumerate() {
+            let empty = Line::new();
+            let line = lines.get(line_index).unwrap_or(&empty);
+            let (before, after) = self
+                .column_at(index)
+                .align
+                .split(width - layout::line_width(line));
+            // no padding at the outer edges if there is no border
+            let padding_before = if index == 0 && vertical.is_none() {
+                0
+            } else {
+                self.padding
+            };
+
+            layout::push_run(out, &" ".repeat(padding_before + before), unstyled);
+            for (content, style) in line {
+                layout::push_run(out, content, *style);
+            }
+            layout::push_run(out, &" ".repeat(after + self.padding), unstyled);
+            if let Some(vertical) = &vertical {
+                layout::push_run(out, vertical, self.border_style);
+            }
+        }
+
+        if vertical.is_none() {
+            // remove the padding of the last cells, which would be trailing spaces
+            while let Some((content, style)) = out.last_mut() {
+                if *style != unstyled {
+                    break;
+                }
+                let len = content.trim_end_matches(' ').len();
+                content.truncate(len);
+                if !content.is_empty() {
+                    break;
+                }
+                out.pop();
+            }
+        }
+    }
+
+    /// Appends a horizontal line across the whole table, on its own line.
+    fn push_rule(
+        &self,
+        out: &mut Line,
+        widths: &[usize],
+        (left, cross, right): (char, char, char),
+    ) {
+        let Some(chars) = self.border else {
+            return;
+        };
+        if !out.is_empty() {
+            layout::push_run(out, "\n", MessageStyle::new());
+        }
+        let mut rule = String::from(left);
+        for (index, width) in widths.iter().enumerate() {
+            if index > 0 {
+                rule.push(cross);
+            }
+            rule.extend(std::iter::repeat(chars.horizontal).take(width + self.padding * 2));
+        }
+        rule.push(right);
+        layout::push_run(out, &rule, self.border_style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{layout::Alignment, Color32, Message, MessageStyle, Styleable};
+
+    use super::{BorderChars, Table};
+
+    #[test]
+    fn no_border() {
+        let table = Table::new()
+            .row(["Name", "HP", "Class"])
+            .row(["alice", "100"])
+            .align(1, Alignment::Right)
+            .align(2, Alignment::Center);
+        assert_eq!(
+            "Name    HP  Class\nalice  100",
+            table.to_message().to_string(),
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        let table = Table::new()
+            .row(["1", "a long description\nwith a break"])
+            .max_width(1, 8)
+            .padding(0)
+            .border(BorderChars::LIGHT);
+        assert_eq!(
+            "\
+┌─┬────────┐
+│1│a long  │
+│ │descript│
+│ │ion     │
+│ │with a  │
+│ │break   │
+└─┴────────┘",
+            table.to_message().to_string(),
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let border = MessageStyle::new().color(Color32::GRAY);
+        let table = Table::new()
+            .header(["a".bold()])
+            .row(["日本".color(Color32::RED)])
+            .border(BorderChars::ASCII)
+            .border_style(border)
+            .padding(0);
+        let msg = table.to_message();
+        assert_eq!("+----+\n|a   |\n+----+\n|日本|\n+----+", msg.to_string());
+        assert_eq!(
+            Message::from_segments([
+                ("+----+", border),
+                ("\n", MessageStyle::new()),
+                ("|", border),
+                ("a", MessageStyle::new().bold()),
+                ("   ", MessageStyle::new()),
+                ("|", border),
+                ("\n", MessageStyle::new()),
+                ("+----+", border),
+                ("\n", MessageStyle::new()),
+                ("|", border),
+                ("日本", MessageStyle::new().color(Color32::RED)),
+                ("|", border),
+                ("\n", MessageStyle::new()),
+                ("+----+", border),
+            ]),
+            msg,
+        );
+        assert_eq!("", Table::new().to_message().to_string());
+    }
+}
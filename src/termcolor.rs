@@ -1,27 +1,65 @@
 //! Features for writing out text messages as text using ANSI color codes, using [`termcolor`].
 
+use std::io;
+
 use termcolor::{Color, ColorSpec, WriteColor};
 
-use crate::{util::StackFlattener, Message};
+use crate::{render::Renderer, Message, MessageStyle};
+
+/// Renders [`Message`]s to a [`termcolor::WriteColor`] object.
+///
+/// The writer's color is reset at the end of each message.
+///
+/// [`MessageStyle::size`] and [`MessageStyle::family`] are ignored, as described in
+/// [`FontSize`](crate::FontSize).
+#[derive(Debug)]
+pub struct ColorRenderer<W> {
+    writer: W,
+}
+
+impl<W: WriteColor> ColorRenderer<W> {
+    /// Creates a renderer which writes to `writer`.
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Unwraps this renderer, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: WriteColor> Renderer for ColorRenderer<W> {
+    type Error = io::Error;
+
+    fn segment(&mut self, content: &str, style: MessageStyle) -> io::Result<()> {
+        self.writer.set_color(
+            ColorSpec::new()
+                .set_fg(style.color.map(|c| Color::Rgb(c.r(), c.g(), c.b())))
+                .set_bold(style.bold == Some(true))
+                .set_italic(style.italic == Some(true))
+                .set_underline(style.underline == Some(true))
+                .set_strikethrough(style.strikethrough == Some(true)),
+        )?;
+        write!(self.writer, "{}", content)
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        self.writer.reset()
+    }
+}
 
 impl Message {
     /// Writes this text message as a colored message to a [`termcolor::WriteColor`] object.
     ///
-    /// This uses [`Text::flatten`] to convert from a node hierarchy to a linear sequence of
-    /// [`ColorSpec`]s and messages.
-    pub fn write<W: WriteColor>(&self, writer: &mut W) {
-        let mut flattener = StackFlattener::new(|content, style| {
-            let _ = writer.set_color(
-                ColorSpec::new()
-                    .set_fg(style.color.map(|c| Color::Rgb(c.r(), c.g(), c.b())))
-                    .set_bold(style.bold == Some(true))
-                    .set_italic(style.italic == Some(true))
-                    .set_underline(style.underline == Some(true))
-                    .set_strikethrough(style.strikethrough == Some(true)),
-            );
-            let _ = write!(writer, "{}", content);
-        });
-        self.flatten(&mut flattener);
+    /// This uses [`Message::render`] with a [`ColorRenderer`], so the writer's color is always
+    /// reset at the end, even if writing fails part way through.
+    ///
+    /// # Errors
+    ///
+    /// Errors if setting the color of, or writing to, the writer fails.
+    pub fn write<W: WriteColor>(&self, writer: &mut W) -> io::Result<()> {
+        self.render(&mut ColorRenderer::new(writer))
     }
 }
 
@@ -29,7 +67,7 @@ impl Message {
 mod tests {
     use ecolor::Color32;
     use std::io::Write;
-    use termcolor::{ColorChoice, StandardStream, WriteColor};
+    use termcolor::{Ansi, ColorChoice, StandardStream};
 
     use crate::{IntoMessage, Styleable};
 
@@ -43,8 +81,14 @@ mod tests {
             .with(" EVERYTHING".bold().italic().underline().strikethrough());
 
         let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-        text.write(&mut stdout);
-        let _ = writeln!(&mut stdout);
-        let _ = stdout.reset();
+        text.write(&mut stdout).unwrap();
+        writeln!(&mut stdout).unwrap();
+    }
+
+    #[test]
+    fn resets() {
+        let mut writer = Ansi::new(Vec::new());
+        "a".color(Color32::RED).write(&mut writer).unwrap();
+        assert!(writer.into_inner().ends_with(b"a\x1b[0m"));
     }
 }
@@ -1,10 +1,14 @@
 //! The core objects used by the library.
 
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+};
 
 use itertools::Itertools;
 
-use crate::{util::MessageFlattener, Color32};
+use crate::{sanitize, util::MessageFlattener, Color32};
 
 // core types
 
@@ -64,6 +68,15 @@ use crate::{util::MessageFlattener, Color32};
 /// // Or use `no_X()` to disable the decoration `X`
 /// let msg = "Italic text, ".italic()
 ///     .with("not italic anymore".no_italic());
+///
+/// // Change the size and font family of text, if the output supports it
+/// use expedition::{FontFamily, FontSize};
+///
+/// let msg = "Heading".size(FontSize::Scale(2.0))
+///     .with("\n")
+///     .with("code".family(FontFamily::Monospace))
+///     .with("\n")
+///     .with("footnote".size(FontSize::Points(8.0)));
 /// ```
 ///
 /// # Output
@@ -88,9 +101,15 @@ use crate::{util::MessageFlattener, Color32};
 ///
 /// See the documentation for the crate features to see usage info for specific output formats.
 ///
+/// # Deep hierarchies
+///
+/// Cloning, comparing, hashing and dropping a message do not recurse into its children, so they
+/// are safe to use on messages of any depth. [`fmt::Debug`] and serialization with `serde` still
+/// recurse.
+///
 /// [`egui`]: https://docs.rs/egui
 /// [`LayoutJob`]: https://docs.rs/egui/text/struct.LayoutJob.html
-#[derive(Clone, PartialEq, Eq, Hash, Default)]
+#[derive(Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Message {
     /// What text this object holds.
@@ -98,7 +117,7 @@ pub struct Message {
     /// Decoration and formatting applied to this text message.
     pub style: MessageStyle,
     /// Child text messages added on to this text.
-    pub children: Vec<Message>,
+    pub children: Vec<Self>,
 }
 
 /// Styling that is currently applied to the contents of a [`Message`].
@@ -119,6 +138,46 @@ pub struct MessageStyle {
     pub underline: Option<bool>,
     /// Strikethrough decoration.
     pub strikethrough: Option<bool>,
+    /// Size of the font.
+    ///
+    /// Not all outputs support changing the font size, so this may be ignored - see the
+    /// documentation of each output for how it is handled.
+    pub size: Option<FontSize>,
+    /// Family of the font.
+    ///
+    /// Like [`MessageStyle::size`], this may be ignored by some outputs.
+    pub family: Option<FontFamily>,
+}
+
+/// Size of the font used to display a [`Message`].
+///
+/// Since font sizes are floating-point numbers, equality and hashing compare the exact bits of
+/// the number, so that this can be used in a [`MessageStyle`].
+///
+/// Terminals cannot change the font of individual characters, so terminal outputs ignore both
+/// the size and the [`FontFamily`] of text.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontSize {
+    /// An absolute size in points.
+    Points(f32),
+    /// A size relative to the default font size of the output, where `1.0` is the default size.
+    ///
+    /// Note that this is not relative to the size of the parent node.
+    Scale(f32),
+}
+
+/// Family of the font used to display a [`Message`].
+///
+/// This is only a hint, and outputs will use whichever of their fonts best matches the family.
+/// Like the [`FontSize`], it is ignored by terminal outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontFamily {
+    /// A font where characters have different widths, used for most text.
+    Proportional,
+    /// A font where all characters have the same width, used for code.
+    Monospace,
 }
 
 impl Message {
@@ -155,6 +214,8 @@ impl MessageStyle {
         self.italic = from.italic.or(self.italic);
         self.underline = from.underline.or(self.underline);
         self.strikethrough = from.strikethrough.or(self.strikethrough);
+        self.size = from.size.or(self.size);
+        self.family = from.family.or(self.family);
     }
 
     /// Creates a new style which is the result of merging `from` on top of `self`, using
@@ -167,6 +228,104 @@ impl MessageStyle {
     }
 }
 
+impl FontSize {
+    /// Gets the size in points, given the default font size of the output.
+    #[must_use]
+    pub fn resolve(self, default_size: f32) -> f32 {
+        match self {
+            Self::Points(size) => size,
+            Self::Scale(scale) => default_size * scale,
+        }
+    }
+
+    fn bits(self) -> (u8, u32) {
+        match self {
+            Self::Points(size) => (0, size.to_bits()),
+            Self::Scale(scale) => (1, scale.to_bits()),
+        }
+    }
+}
+
+impl PartialEq for FontSize {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for FontSize {}
+
+impl Hash for FontSize {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+// the derived implementations recurse into children, which would overflow the stack for
+// extremely deep messages
+
+impl Clone for Message {
+    fn clone(&self) -> Self {
+        fn shallow(node: &Message) -> Message {
+            Message {
+                content: node.content.clone(),
+                style: node.style,
+                children: Vec::with_capacity(node.children.len()),
+            }
+        }
+
+        let mut stack = vec![(shallow(self), self.children.iter())];
+        loop {
+            let (_, children) = stack.last_mut().expect("stack should never be empty here");
+            if let Some(child) = children.next() {
+                stack.push((shallow(child), child.children.iter()));
+                continue;
+            }
+
+            let (node, _) = stack.pop().expect("stack should never be empty here");
+            match stack.last_mut() {
+                Some((parent, _)) => parent.children.push(node),
+                None => return node,
+            }
+        }
+    }
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some((a, b)) = stack.pop() {
+            if a.content != b.content || a.style != b.style || a.children.len() != b.children.len()
+            {
+                return false;
+            }
+            stack.extend(a.children.iter().zip(&b.children));
+        }
+        true
+    }
+}
+
+impl Eq for Message {}
+
+impl Hash for Message {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            node.content.hash(state);
+            node.style.hash(state);
+            node.children.len().hash(state);
+            stack.extend(node.children.iter().rev());
+        }
+    }
+}
+
+impl Drop for Message {
+    fn drop(&mut self) {
+        if !self.children.is_empty() {
+            sanitize::drop_iteratively(mem::take(&mut self.children));
+        }
+    }
+}
+
 // text traits
 
 /// Used to create a [`Message`].
@@ -222,6 +381,12 @@ pub trait Styleable {
     /// Changes the strikethrough state.
     fn with_strikethrough(self, state: Option<bool>) -> Self::Out;
 
+    /// Changes the font size.
+    fn with_size(self, size: Option<FontSize>) -> Self::Out;
+
+    /// Changes the font family.
+    fn with_family(self, family: Option<FontFamily>) -> Self::Out;
+
     /// Sets a color.
     fn color(self, color: Color32) -> Self::Out
     where
@@ -293,6 +458,22 @@ pub trait Styleable {
     {
         self.with_strikethrough(Some(false))
     }
+
+    /// Sets a font size.
+    fn size(self, size: FontSize) -> Self::Out
+    where
+        Self: Sized,
+    {
+        self.with_size(Some(size))
+    }
+
+    /// Sets a font family.
+    fn family(self, family: FontFamily) -> Self::Out
+    where
+        Self: Sized,
+    {
+        self.with_family(Some(family))
+    }
 }
 
 impl Styleable for MessageStyle {
@@ -326,6 +507,16 @@ impl Styleable for MessageStyle {
         self.strikethrough = state;
         self
     }
+
+    fn with_size(mut self, size: Option<FontSize>) -> Self::Out {
+        self.size = size;
+        self
+    }
+
+    fn with_family(mut self, family: Option<FontFamily>) -> Self::Out {
+        self.family = family;
+        self
+    }
 }
 
 impl<T: Into<Message>> Styleable for T {
@@ -366,6 +557,18 @@ impl<T: Into<Message>> Styleable for T {
         text.style.strikethrough = state;
         text
     }
+
+    fn with_size(self, size: Option<FontSize>) -> Self::Out {
+        let mut text = self.into();
+        text.style.size = size;
+        text
+    }
+
+    fn with_family(self, family: Option<FontFamily>) -> Self::Out {
+        let mut text = self.into();
+        text.style.family = family;
+        text
+    }
 }
 
 // display + debug
@@ -402,26 +605,28 @@ impl fmt::Debug for Message {
 
 impl fmt::Debug for MessageStyle {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fn decoration(state: Option<bool>, name: &'static str) -> Option<String> {
-            state.map(|value| {
-                if value {
-                    name.to_owned()
-                } else {
-                    format!("!{}", name)
-                }
-            })
+        fn decoration(value: bool, name: &'static str) -> String {
+            if value {
+                name.to_owned()
+            } else {
+                format!("!{}", name)
+            }
         }
 
         let color = self.color.map(|color| format!("{:?}", color));
-        let bold = decoration(self.bold, "Bold");
-        let italic = decoration(self.italic, "Italic");
-        let underline = decoration(self.underline, "Underline");
-        let strikethrough = decoration(self.strikethrough, "Strikethrough");
+        let bold = self.bold.map(|value| decoration(value, "Bold"));
+        let italic = self.italic.map(|value| decoration(value, "Italic"));
+        let underline = self.underline.map(|value| decoration(value, "Underline"));
+        let strikethrough = self
+            .strikethrough
+            .map(|value| decoration(value, "Strikethrough"));
+        let size = self.size.map(|size| format!("{:?}", size));
+        let family = self.family.map(|family| format!("{:?}", family));
 
         write!(
             f,
             "{}",
-            [color, bold, italic, underline, strikethrough]
+            [color, bold, italic, underline, strikethrough, size, family]
                 .into_iter()
                 .flatten()
                 .join(" + "),
@@ -466,7 +671,9 @@ mod tests {
                     bold: None,
                     italic: None,
                     underline: None,
-                    strikethrough: None
+                    strikethrough: None,
+                    size: None,
+                    family: None,
                 },
                 children: Vec::new(),
             },
@@ -0,0 +1,338 @@
+//! Features for formatting [`tracing`] events as text messages, using a [`tracing_subscriber`]
+//! [`Layer`].
+//!
+//! A [`MessageLayer`] converts each event into a [`Message`] using an [`EventFormat`], and sends
+//! it to a [`MessageSink`], which may write it to a terminal or store it for display in an
+//! in-app log console.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{mpsc, Mutex};
+//! use tracing_subscriber::prelude::*;
+//! use expedition::tracing::MessageLayer;
+//!
+//! let (send, recv) = mpsc::channel();
+//! let subscriber = tracing_subscriber::registry().with(MessageLayer::new(Mutex::new(send)));
+//!
+//! tracing::subscriber::with_default(subscriber, || {
+//!     tracing::info!(player = "alice", "player joined");
+//! });
+//!
+//! let (_level, msg) = recv.try_recv().unwrap();
+//! assert_eq!(
+//!     "INFO rust_out: player joined player=alice",
+//!     msg.to_string(),
+//! );
+//! ```
+
+use std::{
+    fmt::{self, Write},
+    sync::{mpsc, Arc, Mutex},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, Layer};
+
+use crate::{
+    console::{MessageLog, Severity},
+    Color32, IntoMessage, Message, MessageStyle, Styleable,
+};
+
+/// Destination for the messages created by a [`MessageLayer`].
+///
+/// This is implemented for:
+/// - closures taking the level and message of an event
+/// - [`mpsc::Sender`]s of the level and message behind a [`Mutex`], which can be drained by e.g. a
+///   UI thread (a bare [`mpsc::Sender`] is only [`Sync`] since Rust 1.72)
+/// - shared [`MessageLog`]s, which can be shown by an in-app console
+/// - [`TermcolorSink`], if the `termcolor` feature is enabled
+pub trait MessageSink: Send + Sync + 'static {
+    /// Sends the message created from an event at `level`.
+    fn send(&self, level: Level, message: Message);
+}
+
+impl<F> MessageSink for F
+where
+    F: Fn(Level, Message) + Send + Sync + 'static,
+{
+    fn send(&self, level: Level, message: Message) {
+        self(level, message);
+    }
+}
+
+impl MessageSink for Mutex<mpsc::Sender<(Level, Message)>> {
+    fn send(&self, level: Level, message: Message) {
+        // sending cannot leave the sender in an invalid state
+        let sender = self.lock().unwrap_or_else(|err| err.into_inner());
+        // the receiver being dropped is not an error for the code being traced
+        let _ = sender.send((level, message));
+    }
+}
+
+impl MessageSink for Arc<Mutex<MessageLog>> {
+    fn send(&self, level: Level, message: Message) {
+        // a panic while holding the lock cannot leave the log in an invalid state
+        let mut log = self.lock().unwrap_or_else(|err| err.into_inner());
+        log.push(level.into(), message);
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::ERROR => Self::Error,
+            Level::WARN => Self::Warn,
+            Level::INFO => Self::Info,
+            Level::DEBUG => Self::Debug,
+            Level::TRACE => Self::Trace,
+        }
+    }
+}
+
+/// Defines how a [`tracing`] [`Event`] is converted into a [`Message`].
+///
+/// Events are formatted as the level, the target, the `message` field, then all other fields
+/// as `name=value` pairs, each with their own style.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventFormat {
+    /// Style of the level of [`Level::ERROR`] events.
+    pub error: MessageStyle,
+    /// Style of the level of [`Level::WARN`] events.
+    pub warn: MessageStyle,
+    /// Style of the level of [`Level::INFO`] events.
+    pub info: MessageStyle,
+    /// Style of the level of [`Level::DEBUG`] events.
+    pub debug: MessageStyle,
+    /// Style of the level of [`Level::TRACE`] events.
+    pub trace: MessageStyle,
+    /// Style of the target of the event.
+    pub target: MessageStyle,
+    /// Style of the `message` field.
+    pub message: MessageStyle,
+    /// Style of the names of other fields.
+    pub field_name: MessageStyle,
+    /// Style of the values of other fields.
+    pub field_value: MessageStyle,
+    /// Whether to include the level.
+    pub show_level: bool,
+    /// Whether to include the target.
+    pub show_target: bool,
+}
+
+impl Default for EventFormat {
+    fn default() -> Self {
+        Self {
+            error: Severity::Error.style().bold(),
+            warn: Severity::Warn.style().bold(),
+            info: Severity::Info.style(),
+            debug: Severity::Debug.style(),
+            trace: Severity::Trace.style(),
+            target: MessageStyle::new().color(Color32::DARK_GRAY),
+            message: MessageStyle::new(),
+            field_name: MessageStyle::new().italic(),
+            field_value: MessageStyle::new(),
+            show_level: true,
+            show_target: true,
+        }
+    }
+}
+
+impl EventFormat {
+    /// Gets the style of the level of events at `level`.
+    #[must_use]
+    pub const fn level_style(&self, level: Level) -> MessageStyle {
+        match level {
+            Level::ERROR => self.error,
+            Level::WARN => self.warn,
+            Level::INFO => self.info,
+            Level::DEBUG => self.debug,
+            Level::TRACE => self.trace,
+        }
+    }
+
+    /// Converts an event into a message.
+    pub fn format(&self, event: &Event<'_>) -> Message {
+        let metadata = event.metadata();
+        let mut fields = FieldVisitor::default();
+        event.record(&mut fields);
+
+        let mut parts = Vec::new();
+        if self.show_level {
+            parts.push(
+                metadata
+                    .level()
+                    .as_str()
+                    .with_style(self.level_style(*metadata.level())),
+            );
+        }
+        if self.show_target {
+            parts.push(format!("{}:", metadata.target()).with_style(self.target));
+        }
+        if let Some(message) = fields.message {
+            parts.push(message.with_style(self.message));
+        }
+        for (name, value) in fields.fields {
+            parts.push(
+                Message::default()
+                    .with(name.with_style(self.field_name))
+                    .with("=")
+                    .with(value.with_style(self.field_value)),
+            );
+        }
+
+        let mut msg = Message::default();
+        for (i, part) in parts.into_iter().enumerate() {
+            if i > 0 {
+                msg = msg.with(" ");
+            }
+            msg = msg.with(part);
+        }
+        msg
+    }
+}
+
+/// Collects the fields of an event as text.
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_owned());
+        } else {
+            self.fields.push((field.name(), value.to_owned()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut text = String::new();
+        let _ = write!(text, "{:?}", value);
+        if field.name() == "message" {
+            self.message = Some(text);
+        } else {
+            self.fields.push((field.name(), text));
+        }
+    }
+}
+
+/// [`Layer`] which formats events as [`Message`]s, and sends them to a [`MessageSink`].
+///
+/// Spans are not included in the created messages.
+#[derive(Debug)]
+pub struct MessageLayer<K> {
+    sink: K,
+    format: EventFormat,
+}
+
+impl<K: MessageSink> MessageLayer<K> {
+    /// Creates a layer which sends messages to `sink`, using the default [`EventFormat`].
+    pub fn new(sink: K) -> Self {
+        Self {
+            sink,
+            format: EventFormat::default(),
+        }
+    }
+
+    /// Sets how events are formatted.
+    #[must_use]
+    pub const fn format(mut self, format: EventFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl<S: Subscriber, K: MessageSink> Layer<S> for MessageLayer<K> {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let message = self.format.format(event);
+        self.sink.send(*event.metadata().level(), message);
+    }
+}
+
+/// [`MessageSink`] which writes messages to standard error, using [`Message::write`].
+///
+/// Errors from writing are ignored, since there is nowhere to report them to.
+#[cfg(feature = "termcolor")]
+#[derive(Debug)]
+pub struct TermcolorSink {
+    stream: termcolor::StandardStream,
+}
+
+#[cfg(feature = "termcolor")]
+impl TermcolorSink {
+    /// Creates a sink writing to standard error, using `choice` to decide whether to use
+    /// colors.
+    #[must_use]
+    pub fn stderr(choice: termcolor::ColorChoice) -> Self {
+        Self {
+            stream: termcolor::StandardStream::stderr(choice),
+        }
+    }
+}
+
+#[cfg(feature = "termcolor")]
+impl MessageSink for TermcolorSink {
+    fn send(&self, _: Level, message: Message) {
+        let mut stream = self.stream.lock();
+        let _ = message.write(&mut stream);
+        let _ = std::io::Write::write_all(&mut stream, b"\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::Level;
+    use tracing_subscriber::prelude::*;
+
+    use crate::{Color32, IntoMessage, Message, MessageStyle, Styleable};
+
+    use super::{EventFormat, MessageLayer};
+
+    #[test]
+    fn format() {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let messages = Arc::clone(&messages);
+            move |level, msg| messages.lock().unwrap().push((level, msg))
+        };
+        let format = EventFormat {
+            show_target: false,
+            ..Default::default()
+        };
+        let subscriber =
+            tracing_subscriber::registry().with(MessageLayer::new(sink).format(format));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(count = 3, "low on {}", "health");
+            tracing::error!(name = "x");
+        });
+
+        let messages = std::mem::take(&mut *messages.lock().unwrap());
+        assert_eq!(
+            (
+                Level::WARN,
+                Message::default()
+                    .with("WARN".color(Color32::YELLOW).bold())
+                    .with(" ")
+                    .with("low on health".with_style(MessageStyle::new()))
+                    .with(" ")
+                    .with(
+                        Message::default()
+                            .with("count".italic())
+                            .with("=")
+                            .with("3".with_style(MessageStyle::new()))
+                    ),
+            ),
+            messages[0],
+        );
+        assert_eq!("ERROR name=x", messages[1].1.to_string());
+    }
+}
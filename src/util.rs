@@ -1,43 +1,651 @@
 //! Utilities for manipulating text and text hierarchies.
 
-use crate::{Message, MessageStyle};
+use std::{
+    convert::Infallible,
+    iter::FusedIterator,
+    mem,
+    ops::{ControlFlow, Range},
+    slice, vec,
+};
+
+use crate::{Message, MessageStyle, Styleable};
 
 impl Message {
     /// Allows flattening a hierarchy of message nodes into a linear sequence of styles and strings.
     ///
     /// This function traverses the tree of message nodes via a depth-first method, starting
     /// with `self` as the root, and on each node providing the `flattener` with:
+    /// - [`MessageFlattener::enter_node`]: the [`NodeContext`] of the node entered
     /// - [`MessageFlattener::push_style`]: the style of the node entered
-    /// - [`MessageFlattener::content`]: the content of the node
+    /// - [`MessageFlattener::node_content`]: the [`NodeContext`] of the node, which by default
+    ///   calls [`MessageFlattener::content`] with the content of the node
     /// - [`MessageFlattener::pop_style`]: the style of the node exited
+    /// - [`MessageFlattener::exit_node`]: the [`NodeContext`] of the node exited
     ///
     /// The push/pop functions can be used to implement a style stack, from which the topmost
     /// style can be computed. Combining this with the content, you effectively have access to
     /// the entire styled message, which you can use for outputting.
     ///
     /// To see an implemention which already has the style stack, see [`StackFlattener`].
+    ///
+    /// The traversal uses an explicit stack instead of recursion, so the depth of the hierarchy
+    /// does not affect the amount of call stack used.
     pub fn flatten<F: MessageFlattener>(&self, flattener: &mut F) {
-        flattener.push_style(self.style);
+        match self.try_flatten(&mut Infallibly(flattener)) {
+            ControlFlow::Continue(()) => {}
+            ControlFlow::Break(never) => match never {},
+        }
+    }
 
-        flattener.content(&self.content);
-        for child in &self.children {
-            child.flatten(flattener);
+    /// Like [`Message::flatten`], but allows the `flattener` to stop the traversal early by
+    /// returning [`ControlFlow::Break`] from any of its functions.
+    ///
+    /// Returns the value that the traversal was stopped with, if any. No more functions of the
+    /// flattener are called after it breaks, so styles which were pushed are not popped.
+    ///
+    /// # Examples
+    ///
+    /// Truncating a message after a number of characters:
+    ///
+    /// ```
+    /// # use std::ops::ControlFlow;
+    /// # use expedition::{util::TryStackFlattener, Color32, IntoMessage, Styleable};
+    /// let msg = "Hello "
+    ///     .with("world".color(Color32::RED))
+    ///     .with(", this is a long message");
+    ///
+    /// let mut truncated = String::new();
+    /// let mut flattener = TryStackFlattener::new(|content: &str, _| {
+    ///     for c in content.chars() {
+    ///         if truncated.len() == 8 {
+    ///             return ControlFlow::Break(());
+    ///         }
+    ///         truncated.push(c);
+    ///     }
+    ///     ControlFlow::Continue(())
+    /// });
+    /// assert!(msg.try_flatten(&mut flattener).is_break());
+    /// assert_eq!("Hello wo", truncated);
+    /// ```
+    ///
+    /// Propagating errors:
+    ///
+    /// ```
+    /// # use std::{fmt::Write, ops::ControlFlow};
+    /// # use expedition::{util::TryStackFlattener, IntoMessage};
+    /// fn write_plain(msg: &expedition::Message, out: &mut impl Write) -> std::fmt::Result {
+    ///     let mut flattener = TryStackFlattener::new(|content: &str, _| {
+    ///         match out.write_str(content) {
+    ///             Ok(()) => ControlFlow::Continue(()),
+    ///             Err(err) => ControlFlow::Break(err),
+    ///         }
+    ///     });
+    ///     match msg.try_flatten(&mut flattener) {
+    ///         ControlFlow::Continue(()) => Ok(()),
+    ///         ControlFlow::Break(err) => Err(err),
+    ///     }
+    /// }
+    ///
+    /// let mut out = String::new();
+    /// write_plain(&"a".with("b"), &mut out).unwrap();
+    /// assert_eq!("ab", out);
+    /// ```
+    pub fn try_flatten<F: TryMessageFlattener>(&self, flattener: &mut F) -> ControlFlow<F::Break> {
+        let mut path = Vec::new();
+        let mut offset = 0;
+        let root = NodeContext {
+            node: self,
+            depth: 0,
+            path: &path,
+            offset,
+        };
+        flattener.enter_node(&root)?;
+        flattener.push_style(self.style)?;
+        flattener.node_content(&root)?;
+        offset += self.content.len();
+
+        let mut stack = vec![(self, self.children.iter().enumerate(), 0)];
+        while let Some((node, children, node_offset)) = stack.last_mut() {
+            match children.next() {
+                Some((index, child)) => {
+                    path.push(index);
+                    let cx = NodeContext {
+                        node: child,
+                        depth: path.len(),
+                        path: &path,
+                        offset,
+                    };
+                    flattener.enter_node(&cx)?;
+                    flattener.push_style(child.style)?;
+                    flattener.node_content(&cx)?;
+                    stack.push((child, child.children.iter().enumerate(), offset));
+                    offset += child.content.len();
+                }
+                None => {
+                    let (node, node_offset) = (*node, *node_offset);
+                    flattener.pop_style(node.style)?;
+                    flattener.exit_node(&NodeContext {
+                        node,
+                        depth: path.len(),
+                        path: &path,
+                        offset: node_offset,
+                    })?;
+                    stack.pop();
+                    path.pop();
+                }
+            }
         }
+        ControlFlow::Continue(())
+    }
 
-        flattener.pop_style(self.style);
+    /// Creates an iterator over the flattened content of this message, along with the final
+    /// merged style of each piece of content.
+    ///
+    /// This yields the same sequence of content and styles that a [`StackFlattener`] would be
+    /// given, including nodes with empty content, but allows using iterator adapters instead of
+    /// implementing a [`MessageFlattener`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{Color32, IntoMessage, MessageStyle, Styleable};
+    /// let msg = "Unstyled "
+    ///     .with("red ".color(Color32::RED)
+    ///         .with("red and bold".bold()));
+    ///
+    /// let red: String = msg
+    ///     .segments()
+    ///     .filter(|(_, style)| style.color == Some(Color32::RED))
+    ///     .map(|(content, _)| content)
+    ///     .collect();
+    /// assert_eq!("red red and bold", red);
+    /// ```
+    pub const fn segments(&self) -> Segments<'_> {
+        Segments {
+            root: Some(self),
+            stack: Vec::new(),
+        }
+    }
+}
+
+impl Message {
+    /// Gets the node at the given index path, where each index is an index into the
+    /// [`Message::children`] of the previous node, starting from `self`.
+    ///
+    /// An empty path refers to `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Message};
+    /// let msg = "a"
+    ///     .with("b")
+    ///     .with("c".with("d"));
+    ///
+    /// assert_eq!(Some(&msg), msg.node_at(&[]));
+    /// assert_eq!(Some(&Message::new("d")), msg.node_at(&[1, 0]));
+    /// assert_eq!(None, msg.node_at(&[2]));
+    /// ```
+    pub fn node_at(&self, path: &[usize]) -> Option<&Self> {
+        path.iter()
+            .try_fold(self, |node, &index| node.children.get(index))
+    }
+
+    /// Gets a mutable reference to the node at the given index path.
+    ///
+    /// See [`Message::node_at`].
+    pub fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut Self> {
+        path.iter()
+            .try_fold(self, |node, &index| node.children.get_mut(index))
+    }
+}
+
+impl Message {
+    /// Traverses the tree of message nodes mutably, allowing the nodes to be modified in place.
+    ///
+    /// This function traverses the tree via a depth-first method, starting with `self` as the
+    /// root, and on each node calls:
+    /// - [`MessageVisitorMut::enter`], before the node's children are visited
+    /// - [`MessageVisitorMut::exit`], after the node's children are visited
+    ///
+    /// Both functions are given the inherited style of the node, which is the merged style of all
+    /// of its ancestors. Changes made to a node in `enter` are visible when its children are
+    /// visited - for example, children added in `enter` will be visited, and the inherited style
+    /// of the children will use the modified style of the node.
+    ///
+    /// Like [`Message::flatten`], this does not use recursion, so the depth of the hierarchy
+    /// does not affect the amount of call stack used.
+    ///
+    /// For simpler transformations, see [`Message::map_nodes`], [`Message::map_styles`],
+    /// [`Message::map_content`] and [`Message::retain`].
+    pub fn visit_mut<V: MessageVisitorMut>(&mut self, visitor: &mut V) {
+        struct Frame {
+            node: Message,
+            inherited: MessageStyle,
+            children: vec::IntoIter<Message>,
+            visited: Vec<Message>,
+        }
+
+        fn enter<V: MessageVisitorMut>(
+            visitor: &mut V,
+            mut node: Message,
+            inherited: MessageStyle,
+        ) -> Frame {
+            visitor.enter(&mut node, inherited);
+            let children = mem::take(&mut node.children);
+            Frame {
+                node,
+                inherited,
+                visited: Vec::with_capacity(children.len()),
+                children: children.into_iter(),
+            }
+        }
+
+        let mut stack = vec![enter(visitor, mem::take(self), MessageStyle::default())];
+        while let Some(top) = stack.last_mut() {
+            match top.children.next() {
+                Some(child) => {
+                    let inherited = top.inherited.merged_from(top.node.style);
+                    stack.push(enter(visitor, child, inherited));
+                }
+                None => {
+                    let Frame {
+                        mut node,
+                        inherited,
+                        visited,
+                        ..
+                    } = stack.pop().expect("stack should not be empty");
+                    node.children = visited;
+                    visitor.exit(&mut node, inherited);
+                    match stack.last_mut() {
+                        Some(parent) => parent.visited.push(node),
+                        None => *self = node,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on every node in this message, in depth-first order, along with the inherited
+    /// style of the node.
+    ///
+    /// See [`Message::visit_mut`] for how the traversal works.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{Color32, IntoMessage, Styleable};
+    /// let mut msg = "Red, ".color(Color32::RED)
+    ///     .with("still red, ")
+    ///     .with("blue".color(Color32::BLUE));
+    ///
+    /// // recolor all red text to green
+    /// msg.map_nodes(|node, inherited| {
+    ///     if inherited.merged_from(node.style).color == Some(Color32::RED) {
+    ///         node.style.color = Some(Color32::GREEN);
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(
+    ///     "Red, ".color(Color32::GREEN)
+    ///         .with("still red, ")
+    ///         .with("blue".color(Color32::BLUE)),
+    ///     msg,
+    /// );
+    /// ```
+    pub fn map_nodes<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Self, MessageStyle),
+    {
+        struct Visitor<F>(F);
+
+        impl<F> MessageVisitorMut for Visitor<F>
+        where
+            F: FnMut(&mut Message, MessageStyle),
+        {
+            fn enter(&mut self, node: &mut Message, inherited: MessageStyle) {
+                (self.0)(node, inherited);
+            }
+        }
+
+        self.visit_mut(&mut Visitor(f));
+    }
+
+    /// Calls `f` on the style of every node in this message, in depth-first order, along with
+    /// the inherited style of the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let mut msg = "Italic, ".italic()
+    ///     .with("bold".bold());
+    ///
+    /// // strip all italic decorations
+    /// msg.map_styles(|style, _| style.italic = None);
+    ///
+    /// assert_eq!("Italic, ".with("bold".bold()), msg);
+    /// ```
+    pub fn map_styles<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut MessageStyle, MessageStyle),
+    {
+        self.map_nodes(|node, inherited| f(&mut node.style, inherited));
+    }
+
+    /// Calls `f` on the content of every node in this message, in depth-first order, along with
+    /// the final merged style of the node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let mut msg = "Oh darn, ".with("darn it".bold());
+    ///
+    /// msg.map_content(|content, _| *content = content.replace("darn", "****"));
+    ///
+    /// assert_eq!("Oh ****, ".with("**** it".bold()), msg);
+    /// ```
+    pub fn map_content<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut String, MessageStyle),
+    {
+        self.map_nodes(|node, inherited| f(&mut node.content, inherited.merged_from(node.style)));
+    }
+
+    /// Removes all nodes for which `f` returns `false`, along with all of their children.
+    ///
+    /// `f` is given each node along with its inherited style, in depth-first order. The root
+    /// node is never removed, and is not passed to `f`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let mut msg = "Visible, "
+    ///     .with("strikethrough, ".strikethrough())
+    ///     .with("visible again");
+    ///
+    /// msg.retain(|node, inherited| inherited.merged_from(node.style).strikethrough != Some(true));
+    ///
+    /// assert_eq!("Visible, ".with("visible again"), msg);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Self, MessageStyle) -> bool,
+    {
+        self.map_nodes(|node, inherited| {
+            let inherited = inherited.merged_from(node.style);
+            node.children.retain(|child| f(child, inherited));
+        });
+    }
+}
+
+impl Message {
+    /// Creates a message from a sequence of content and styles, such as the one produced by
+    /// [`Message::segments`].
+    ///
+    /// Adjacent segments with the same style are merged, and empty segments are skipped. If
+    /// there is a single segment left, it becomes the root node; otherwise, each segment becomes
+    /// a child of an unstyled root node with no content.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Message, MessageStyle, Styleable};
+    /// let msg = Message::from_segments([
+    ///     ("one ", MessageStyle::new()),
+    ///     ("two ", MessageStyle::new()),
+    ///     ("", MessageStyle::new().italic()),
+    ///     ("three", MessageStyle::new().bold()),
+    /// ]);
+    ///
+    /// assert_eq!(
+    ///     Message::default()
+    ///         .with("one two ")
+    ///         .with("three".bold()),
+    ///     msg,
+    /// );
+    /// ```
+    pub fn from_segments<S: AsRef<str>>(
+        segments: impl IntoIterator<Item = (S, MessageStyle)>,
+    ) -> Self {
+        let mut runs: Vec<Self> = Vec::new();
+        for (content, style) in segments {
+            let content = content.as_ref();
+            if content.is_empty() {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(last) if last.style == style => last.content.push_str(content),
+                _ => runs.push(Self::new(content).with_style(style)),
+            }
+        }
+
+        if runs.len() == 1 {
+            runs.pop().expect("there should be exactly one run")
+        } else {
+            Self {
+                content: String::new(),
+                style: MessageStyle::default(),
+                children: runs,
+            }
+        }
+    }
+
+    /// Creates a flat version of this message, where each run of content with the same final
+    /// style is a single node.
+    ///
+    /// This is equivalent to calling [`Message::from_segments`] with the [`Message::segments`] of
+    /// this message, and is useful for comparing messages which look the same when displayed
+    /// but have a different node hierarchy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use expedition::{IntoMessage, Styleable};
+    /// let a = "one ".bold().with("two");
+    /// let b = "one two".bold();
+    /// assert_ne!(a, b);
+    /// assert_eq!(a.normalized(), b.normalized());
+    /// ```
+    #[must_use]
+    pub fn normalized(&self) -> Self {
+        Self::from_segments(self.segments())
+    }
+}
+
+/// Functions called when traversing a hierarchy of [`Message`] nodes mutably using
+/// [`Message::visit_mut`].
+///
+/// This is the mutable counterpart to [`MessageFlattener`]. All functions are given the node
+/// being visited, which can be freely modified, and the inherited style of that node - the
+/// merged style of all of its ancestors, not including the node's own style.
+pub trait MessageVisitorMut {
+    /// Called when a node is entered, before its children are visited.
+    fn enter(&mut self, node: &mut Message, inherited: MessageStyle) {
+        let _ = (node, inherited);
+    }
+
+    /// Called when a node is exited, after its children are visited.
+    fn exit(&mut self, node: &mut Message, inherited: MessageStyle) {
+        let _ = (node, inherited);
+    }
+}
+
+/// Iterator over the flattened content of a [`Message`] and the merged style of each piece of
+/// content.
+///
+/// See [`Message::segments`].
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    root: Option<&'a Message>,
+    stack: Vec<(slice::Iter<'a, Message>, MessageStyle)>,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = (&'a str, MessageStyle);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            self.stack.push((root.children.iter(), root.style));
+            return Some((&root.content, root.style));
+        }
+
+        loop {
+            let (children, style) = self.stack.last_mut()?;
+            match children.next() {
+                Some(child) => {
+                    let style = style.merged_from(child.style);
+                    self.stack.push((child.children.iter(), style));
+                    return Some((&child.content, style));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl FusedIterator for Segments<'_> {}
+
+/// Where a node is in a hierarchy of [`Message`] nodes being flattened.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeContext<'a> {
+    /// The node itself.
+    pub node: &'a Message,
+    /// Number of ancestors of the node, so the root has a depth of 0.
+    pub depth: usize,
+    /// Index of the node in its parent's children, for each node from the root to this node.
+    ///
+    /// This is empty for the root, and can be passed to [`Message::node_at`].
+    pub path: &'a [usize],
+    /// Byte offset of the node's content in the flattened output, which is the concatenated
+    /// content of all nodes before it.
+    pub offset: usize,
+}
+
+impl NodeContext<'_> {
+    /// Byte range of the node's own content in the flattened output.
+    #[must_use]
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.node.content.len()
     }
 }
 
 /// Functions called when flattening a hierarchy of [`Message`] nodes using [`Message::flatten`].
 pub trait MessageFlattener {
+    /// Called when a new node is entered, before its style is pushed.
+    ///
+    /// Does nothing by default.
+    fn enter_node(&mut self, cx: &NodeContext<'_>) {
+        let _ = cx;
+    }
+
     /// Called when a new style is entered.
     fn push_style(&mut self, style: MessageStyle);
 
     /// Called when a new piece of text content is encountered.
     fn content(&mut self, content: &str);
 
+    /// Called with the context of a node when its content is encountered, after its style is
+    /// pushed. This is given the same context as [`MessageFlattener::enter_node`].
+    ///
+    /// Calls [`MessageFlattener::content`] with the content of the node by default.
+    fn node_content(&mut self, cx: &NodeContext<'_>) {
+        self.content(&cx.node.content);
+    }
+
     /// Called when exiting a style that we previously entered.
     fn pop_style(&mut self, style: MessageStyle);
+
+    /// Called when a node is exited, after its style is popped. This is given the same context
+    /// as [`MessageFlattener::enter_node`].
+    ///
+    /// Does nothing by default.
+    fn exit_node(&mut self, cx: &NodeContext<'_>) {
+        let _ = cx;
+    }
+}
+
+/// Functions called when flattening a hierarchy of [`Message`] nodes using
+/// [`Message::try_flatten`], which can stop the traversal early.
+///
+/// See [`MessageFlattener`] for when each function is called.
+pub trait TryMessageFlattener {
+    /// Value that the traversal is stopped with, such as an error.
+    type Break;
+
+    /// Called when a new node is entered, before its style is pushed.
+    ///
+    /// Does nothing by default.
+    fn enter_node(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Self::Break> {
+        let _ = cx;
+        ControlFlow::Continue(())
+    }
+
+    /// Called when a new style is entered.
+    fn push_style(&mut self, style: MessageStyle) -> ControlFlow<Self::Break>;
+
+    /// Called when a new piece of text content is encountered.
+    fn content(&mut self, content: &str) -> ControlFlow<Self::Break>;
+
+    /// Called with the context of a node when its content is encountered, after its style is
+    /// pushed.
+    ///
+    /// Calls [`TryMessageFlattener::content`] with the content of the node by default.
+    fn node_content(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Self::Break> {
+        self.content(&cx.node.content)
+    }
+
+    /// Called when exiting a style that we previously entered.
+    fn pop_style(&mut self, style: MessageStyle) -> ControlFlow<Self::Break>;
+
+    /// Called when a node is exited, after its style is popped.
+    ///
+    /// Does nothing by default.
+    fn exit_node(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Self::Break> {
+        let _ = cx;
+        ControlFlow::Continue(())
+    }
+}
+
+/// Adapts a [`MessageFlattener`] into a [`TryMessageFlattener`] which never breaks.
+struct Infallibly<'a, F>(&'a mut F);
+
+impl<F: MessageFlattener> TryMessageFlattener for Infallibly<'_, F> {
+    type Break = Infallible;
+
+    fn enter_node(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Infallible> {
+        self.0.enter_node(cx);
+        ControlFlow::Continue(())
+    }
+
+    fn exit_node(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Infallible> {
+        self.0.exit_node(cx);
+        ControlFlow::Continue(())
+    }
+
+    fn node_content(&mut self, cx: &NodeContext<'_>) -> ControlFlow<Infallible> {
+        self.0.node_content(cx);
+        ControlFlow::Continue(())
+    }
+
+    fn push_style(&mut self, style: MessageStyle) -> ControlFlow<Infallible> {
+        self.0.push_style(style);
+        ControlFlow::Continue(())
+    }
+
+    fn content(&mut self, content: &str) -> ControlFlow<Infallible> {
+        self.0.content(content);
+        ControlFlow::Continue(())
+    }
+
+    fn pop_style(&mut self, style: MessageStyle) -> ControlFlow<Infallible> {
+        self.0.pop_style(style);
+        ControlFlow::Continue(())
+    }
 }
 
 /// A [`MessageFlattener`] implementation which maintains a stack of [`MessageStyle`]s internally,
@@ -81,7 +689,7 @@ where
 {
     /// Creates a new flattener with an empty style stack, and taking in the consumer that is
     /// called when content is encountered.
-    pub fn new(consumer: F) -> Self {
+    pub const fn new(consumer: F) -> Self {
         Self {
             style_stack: Vec::new(),
             consumer,
@@ -94,21 +702,321 @@ where
     F: FnMut(&str, MessageStyle),
 {
     fn push_style(&mut self, style: MessageStyle) {
-        self.style_stack.push(
-            self.style_stack
-                .last()
-                .unwrap_or(&MessageStyle::default())
-                .merged_from(style),
-        );
+        push_merged(&mut self.style_stack, style);
     }
 
     fn content(&mut self, content: &str) {
-        let default_style = MessageStyle::default();
-        let style = self.style_stack.last().unwrap_or(&default_style);
-        (self.consumer)(content, *style);
+        let style = self.style_stack.last().copied().unwrap_or_default();
+        (self.consumer)(content, style);
+    }
+
+    fn pop_style(&mut self, _: MessageStyle) {
+        self.style_stack.pop();
+    }
+}
+
+/// Like [`StackFlattener`], but the consumer function returns a [`ControlFlow`] which can stop
+/// the traversal of [`Message::try_flatten`].
+#[derive(Debug)]
+pub struct TryStackFlattener<F> {
+    style_stack: Vec<MessageStyle>,
+    consumer: F,
+}
+
+impl<F, B> TryStackFlattener<F>
+where
+    F: FnMut(&str, MessageStyle) -> ControlFlow<B>,
+{
+    /// Creates a new flattener with an empty style stack, and taking in the consumer that is
+    /// called when content is encountered.
+    pub const fn new(consumer: F) -> Self {
+        Self {
+            style_stack: Vec::new(),
+            consumer,
+        }
+    }
+}
+
+impl<F, B> TryMessageFlattener for TryStackFlattener<F>
+where
+    F: FnMut(&str, MessageStyle) -> ControlFlow<B>,
+{
+    type Break = B;
+
+    fn push_style(&mut self, style: MessageStyle) -> ControlFlow<B> {
+        push_merged(&mut self.style_stack, style);
+        ControlFlow::Continue(())
+    }
+
+    fn content(&mut self, content: &str) -> ControlFlow<B> {
+        let style = self.style_stack.last().copied().unwrap_or_default();
+        (self.consumer)(content, style)
+    }
+
+    fn pop_style(&mut self, _: MessageStyle) -> ControlFlow<B> {
+        self.style_stack.pop();
+        ControlFlow::Continue(())
+    }
+}
+
+/// Like [`StackFlattener`], but the consumer function is also given the [`NodeContext`] of the
+/// node that the content is from.
+///
+/// # Examples
+///
+/// Mapping ranges of the flattened output back to nodes:
+///
+/// ```
+/// # use expedition::{util::NodeStackFlattener, Color32, IntoMessage, Styleable};
+/// let msg = "Hello ".with("world".color(Color32::RED)).with("!");
+///
+/// let mut sources = Vec::new();
+/// msg.flatten(&mut NodeStackFlattener::new(|content, _style, cx| {
+///     sources.push((cx.range(), cx.path.to_vec()));
+/// }));
+/// assert_eq!(vec![(0..6, vec![]), (6..11, vec![0]), (11..12, vec![1])], sources);
+/// ```
+#[derive(Debug)]
+pub struct NodeStackFlattener<F> {
+    style_stack: Vec<MessageStyle>,
+    consumer: F,
+}
+
+impl<F> NodeStackFlattener<F>
+where
+    F: FnMut(&str, MessageStyle, &NodeContext<'_>),
+{
+    /// Creates a new flattener with an empty style stack, and taking in the consumer that is
+    /// called when content is encountered.
+    pub const fn new(consumer: F) -> Self {
+        Self {
+            style_stack: Vec::new(),
+            consumer,
+        }
+    }
+}
+
+impl<F> MessageFlattener for NodeStackFlattener<F>
+where
+    F: FnMut(&str, MessageStyle, &NodeContext<'_>),
+{
+    fn push_style(&mut self, style: MessageStyle) {
+        push_merged(&mut self.style_stack, style);
+    }
+
+    fn content(&mut self, _: &str) {}
+
+    fn node_content(&mut self, cx: &NodeContext<'_>) {
+        let style = self.style_stack.last().copied().unwrap_or_default();
+        (self.consumer)(&cx.node.content, style, cx);
     }
 
     fn pop_style(&mut self, _: MessageStyle) {
         self.style_stack.pop();
     }
 }
+
+fn push_merged(style_stack: &mut Vec<MessageStyle>, style: MessageStyle) {
+    let merged = style_stack
+        .last()
+        .copied()
+        .unwrap_or_default()
+        .merged_from(style);
+    style_stack.push(merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        ops::ControlFlow,
+    };
+
+    use super::{MessageFlattener, NodeContext, NodeStackFlattener, TryStackFlattener};
+    use crate::{
+        Color32, IntoMessage, Message, MessageStyle, MessageVisitorMut, StackFlattener, Styleable,
+    };
+
+    #[test]
+    fn node_context() {
+        struct Recorder(Vec<(&'static str, usize, Vec<usize>, usize)>);
+
+        impl MessageFlattener for Recorder {
+            fn enter_node(&mut self, cx: &NodeContext<'_>) {
+                self.0
+                    .push(("enter", cx.depth, cx.path.to_vec(), cx.offset));
+            }
+
+            fn push_style(&mut self, _: MessageStyle) {
+                self.0.push(("push", 0, vec![], 0));
+            }
+
+            fn content(&mut self, _: &str) {}
+
+            fn node_content(&mut self, cx: &NodeContext<'_>) {
+                self.0
+                    .push(("content", cx.depth, cx.path.to_vec(), cx.offset));
+            }
+
+            fn pop_style(&mut self, _: MessageStyle) {}
+
+            fn exit_node(&mut self, cx: &NodeContext<'_>) {
+                self.0.push(("exit", cx.depth, cx.path.to_vec(), cx.offset));
+            }
+        }
+
+        let msg = "ab".with("c".with("de")).with("f");
+        let mut recorder = Recorder(Vec::new());
+        msg.flatten(&mut recorder);
+        assert_eq!(
+            vec![
+                ("enter", 0, vec![], 0),
+                ("push", 0, vec![], 0),
+                ("content", 0, vec![], 0),
+                ("enter", 1, vec![0], 2),
+                ("push", 0, vec![], 0),
+                ("content", 1, vec![0], 2),
+                ("enter", 2, vec![0, 0], 3),
+                ("push", 0, vec![], 0),
+                ("content", 2, vec![0, 0], 3),
+                ("exit", 2, vec![0, 0], 3),
+                ("exit", 1, vec![0], 2),
+                ("enter", 1, vec![1], 5),
+                ("push", 0, vec![], 0),
+                ("content", 1, vec![1], 5),
+                ("exit", 1, vec![1], 5),
+                ("exit", 0, vec![], 0),
+            ],
+            recorder.0,
+        );
+    }
+
+    #[test]
+    fn node_stack_flattener() {
+        let msg = "a"
+            .color(Color32::RED)
+            .with("b".bold().with("c".no_bold()))
+            .with("d");
+        let mut seen = Vec::new();
+        msg.flatten(&mut NodeStackFlattener::new(|content, style, cx| {
+            seen.push((content.to_owned(), style, cx.path.to_vec()));
+        }));
+        let expected: Vec<_> = msg
+            .segments()
+            .zip([vec![], vec![0], vec![0, 0], vec![1]])
+            .map(|((content, style), path)| (content.to_owned(), style, path))
+            .collect();
+        assert_eq!(expected, seen);
+    }
+
+    #[test]
+    fn try_flatten_stops() {
+        let msg = "a".with("b".bold().with("c")).with("d");
+        let mut seen = Vec::new();
+        let mut flattener = TryStackFlattener::new(|content: &str, style| {
+            seen.push(content.to_owned());
+            if style.bold == Some(true) {
+                ControlFlow::Break(content.to_owned())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(
+            ControlFlow::Break("b".to_owned()),
+            msg.try_flatten(&mut flattener)
+        );
+        assert_eq!(vec!["a", "b"], seen);
+    }
+
+    #[test]
+    fn segments_match_flatten() {
+        let msg = "Unstyled "
+            .with("red ".color(Color32::RED).with("bold ".bold()))
+            .with(Message::default().with("italic".italic()))
+            .with(" end");
+
+        let mut flattened = Vec::new();
+        msg.flatten(&mut StackFlattener::new(|content, style| {
+            flattened.push((content.to_owned(), style));
+        }));
+
+        let segments: Vec<(String, MessageStyle)> = msg
+            .segments()
+            .map(|(content, style)| (content.to_owned(), style))
+            .collect();
+        assert_eq!(flattened, segments);
+    }
+
+    #[test]
+    fn visit_mut_order() {
+        #[derive(Default)]
+        struct Visitor {
+            events: Vec<String>,
+        }
+
+        impl MessageVisitorMut for Visitor {
+            fn enter(&mut self, node: &mut Message, inherited: MessageStyle) {
+                self.events
+                    .push(format!("enter {} {:?}", node.content, inherited));
+                node.content.make_ascii_uppercase();
+            }
+
+            fn exit(&mut self, node: &mut Message, inherited: MessageStyle) {
+                self.events
+                    .push(format!("exit {} {:?}", node.content, inherited));
+            }
+        }
+
+        let mut msg = "a".bold().with("b".with("c")).with("d");
+        let mut visitor = Visitor::default();
+        msg.visit_mut(&mut visitor);
+
+        assert_eq!("A".bold().with("B".with("C")).with("D"), msg);
+        assert_eq!(
+            vec![
+                "enter a ",
+                "enter b Bold",
+                "enter c Bold",
+                "exit C Bold",
+                "exit B Bold",
+                "enter d Bold",
+                "exit D Bold",
+                "exit A ",
+            ],
+            visitor.events,
+        );
+    }
+
+    #[test]
+    fn deep() {
+        const DEPTH: usize = 100_000;
+
+        let mut msg = Message::new("leaf").bold();
+        for _ in 1..DEPTH {
+            msg = Message::new("a").with(msg);
+        }
+
+        let mut len = 0;
+        msg.flatten(&mut StackFlattener::new(|content, _| len += content.len()));
+        assert_eq!(DEPTH + 3, len);
+
+        msg.map_content(|content, _| content.push('!'));
+        assert_eq!(
+            Some(("leaf!", MessageStyle::new().bold())),
+            msg.segments().last()
+        );
+
+        let mut copy = msg.clone();
+        assert_eq!(msg, copy);
+        assert_eq!(hash(&msg), hash(&copy));
+        copy.children[0].children[0].children[0].style.italic = Some(true);
+        assert_ne!(msg, copy);
+    }
+
+    fn hash(msg: &Message) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        msg.hash(&mut hasher);
+        hasher.finish()
+    }
+}